@@ -0,0 +1,163 @@
+use crate::object::object::{Builtin, Object};
+
+// the order is significant: compiled code refers to builtins by their index in this table
+pub const BUILTINS: &[Builtin] = &[
+    Builtin { name: "len", function: len },
+    Builtin { name: "puts", function: puts },
+    Builtin { name: "first", function: first },
+    Builtin { name: "last", function: last },
+    Builtin { name: "rest", function: rest },
+    Builtin { name: "push", function: push },
+];
+
+pub fn lookup(name: &str) -> Option<Object> {
+    BUILTINS
+        .iter()
+        .find(|builtin| builtin.name == name)
+        .map(|builtin| Object::Builtin(*builtin))
+}
+
+fn check_arity(arguments: &[Object], want: usize) -> Option<Object> {
+    if arguments.len() != want {
+        return Some(Object::Error(format!(
+            "wrong number of arguments. got={}, want={}",
+            arguments.len(),
+            want
+        )));
+    }
+    None
+}
+
+fn len(arguments: Vec<Object>) -> Object {
+    if let Some(error) = check_arity(&arguments, 1) {
+        return error;
+    }
+
+    match &arguments[0] {
+        Object::String(value) => Object::Integer(value.chars().count() as i64),
+        Object::Array(elements) => Object::Integer(elements.len() as i64),
+        Object::Hash(pairs) => Object::Integer(pairs.len() as i64),
+        other => Object::Error(format!("argument to `len` not supported, got {}", other.type_name())),
+    }
+}
+
+fn puts(arguments: Vec<Object>) -> Object {
+    for argument in arguments {
+        println!("{}", argument);
+    }
+
+    Object::Null
+}
+
+fn array_argument<'a>(name: &str, arguments: &'a [Object]) -> Result<&'a Vec<Object>, Object> {
+    match &arguments[0] {
+        Object::Array(elements) => Ok(elements),
+        other => Err(Object::Error(format!(
+            "argument to `{}` must be ARRAY, got {}",
+            name,
+            other.type_name()
+        ))),
+    }
+}
+
+fn first(arguments: Vec<Object>) -> Object {
+    if let Some(error) = check_arity(&arguments, 1) {
+        return error;
+    }
+
+    match array_argument("first", &arguments) {
+        Ok(elements) => elements.first().cloned().unwrap_or(Object::Null),
+        Err(error) => error,
+    }
+}
+
+fn last(arguments: Vec<Object>) -> Object {
+    if let Some(error) = check_arity(&arguments, 1) {
+        return error;
+    }
+
+    match array_argument("last", &arguments) {
+        Ok(elements) => elements.last().cloned().unwrap_or(Object::Null),
+        Err(error) => error,
+    }
+}
+
+fn rest(arguments: Vec<Object>) -> Object {
+    if let Some(error) = check_arity(&arguments, 1) {
+        return error;
+    }
+
+    match array_argument("rest", &arguments) {
+        Ok(elements) if elements.is_empty() => Object::Null,
+        Ok(elements) => Object::Array(elements[1..].to_vec()),
+        Err(error) => error,
+    }
+}
+
+fn push(arguments: Vec<Object>) -> Object {
+    if let Some(error) = check_arity(&arguments, 2) {
+        return error;
+    }
+
+    match array_argument("push", &arguments) {
+        Ok(elements) => {
+            let mut elements = elements.clone();
+            elements.push(arguments[1].clone());
+            Object::Array(elements)
+        },
+        Err(error) => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, arguments: Vec<Object>) -> Object {
+        match lookup(name) {
+            Some(Object::Builtin(builtin)) => (builtin.function)(arguments),
+            _ => panic!("no builtin named {}", name),
+        }
+    }
+
+    fn array(values: &[i64]) -> Object {
+        Object::Array(values.iter().map(|v| Object::Integer(*v)).collect())
+    }
+
+    fn error(message: &str) -> Object {
+        Object::Error(message.to_string())
+    }
+
+    #[test]
+    fn test_builtins() {
+        let tests = vec![
+            ("len", vec![Object::String("".to_string())], Object::Integer(0)),
+            ("len", vec![Object::String("héllo".to_string())], Object::Integer(5)),
+            ("len", vec![array(&[1, 2, 3])], Object::Integer(3)),
+            ("len", vec![Object::Integer(1)], error("argument to `len` not supported, got INTEGER")),
+            ("len", vec![array(&[]), array(&[])], error("wrong number of arguments. got=2, want=1")),
+            ("first", vec![array(&[1, 2, 3])], Object::Integer(1)),
+            ("first", vec![array(&[])], Object::Null),
+            ("first", vec![Object::Integer(1)], error("argument to `first` must be ARRAY, got INTEGER")),
+            ("last", vec![array(&[1, 2, 3])], Object::Integer(3)),
+            ("last", vec![array(&[])], Object::Null),
+            ("rest", vec![array(&[1, 2, 3])], array(&[2, 3])),
+            ("rest", vec![array(&[1])], array(&[])),
+            ("rest", vec![array(&[])], Object::Null),
+            ("push", vec![array(&[]), Object::Integer(1)], array(&[1])),
+            ("push", vec![array(&[1])], error("wrong number of arguments. got=1, want=2")),
+            ("push", vec![Object::Integer(1), Object::Integer(1)], error("argument to `push` must be ARRAY, got INTEGER")),
+            ("puts", vec![Object::String("hello".to_string())], Object::Null),
+        ];
+
+        for (name, arguments, expected) in tests {
+            assert_eq!(call(name, arguments), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        assert!(lookup("len").is_some());
+        assert!(lookup("nope").is_none());
+    }
+}
@@ -0,0 +1,270 @@
+use crate::token::token::TokenRange;
+
+pub type Instructions = Vec<u8>;
+
+// maps instruction offsets to the source they were compiled from, in increasing offset order; an
+// entry covers every instruction up to the next entry
+pub type Positions = Vec<(usize, TokenRange)>;
+
+// operands are big-endian; the width of each one is given by the opcode's Definition
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Constant,
+    Pop,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    True,
+    False,
+    Null,
+    Equal,
+    NotEqual,
+    GreaterThan,
+    LessThan,
+    Minus,
+    Bang,
+    JumpNotTruthy,
+    Jump,
+    GetGlobal,
+    SetGlobal,
+    Array,
+    Hash,
+    Index,
+    ReturnValue,
+    Return,
+    Call,
+    GetLocal,
+    SetLocal,
+    GetBuiltin,
+    GetFree,
+    Closure,
+    CurrentClosure,
+}
+
+// indexed by the opcode's byte value, see Opcode::from_byte
+const OPCODES: &[Opcode] = &[
+    Opcode::Constant,
+    Opcode::Pop,
+    Opcode::Add,
+    Opcode::Sub,
+    Opcode::Mul,
+    Opcode::Div,
+    Opcode::True,
+    Opcode::False,
+    Opcode::Null,
+    Opcode::Equal,
+    Opcode::NotEqual,
+    Opcode::GreaterThan,
+    Opcode::LessThan,
+    Opcode::Minus,
+    Opcode::Bang,
+    Opcode::JumpNotTruthy,
+    Opcode::Jump,
+    Opcode::GetGlobal,
+    Opcode::SetGlobal,
+    Opcode::Array,
+    Opcode::Hash,
+    Opcode::Index,
+    Opcode::ReturnValue,
+    Opcode::Return,
+    Opcode::Call,
+    Opcode::GetLocal,
+    Opcode::SetLocal,
+    Opcode::GetBuiltin,
+    Opcode::GetFree,
+    Opcode::Closure,
+    Opcode::CurrentClosure,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Definition {
+    pub name: &'static str,
+    pub operand_widths: &'static [usize],
+}
+
+impl Opcode {
+    pub fn from_byte(byte: u8) -> Option<Opcode> {
+        OPCODES.get(byte as usize).copied()
+    }
+
+    pub fn definition(&self) -> Definition {
+        let (name, operand_widths): (&'static str, &'static [usize]) = match self {
+            Opcode::Constant => ("OpConstant", &[2]),
+            Opcode::Pop => ("OpPop", &[]),
+            Opcode::Add => ("OpAdd", &[]),
+            Opcode::Sub => ("OpSub", &[]),
+            Opcode::Mul => ("OpMul", &[]),
+            Opcode::Div => ("OpDiv", &[]),
+            Opcode::True => ("OpTrue", &[]),
+            Opcode::False => ("OpFalse", &[]),
+            Opcode::Null => ("OpNull", &[]),
+            Opcode::Equal => ("OpEqual", &[]),
+            Opcode::NotEqual => ("OpNotEqual", &[]),
+            Opcode::GreaterThan => ("OpGreaterThan", &[]),
+            Opcode::LessThan => ("OpLessThan", &[]),
+            Opcode::Minus => ("OpMinus", &[]),
+            Opcode::Bang => ("OpBang", &[]),
+            Opcode::JumpNotTruthy => ("OpJumpNotTruthy", &[2]),
+            Opcode::Jump => ("OpJump", &[2]),
+            Opcode::GetGlobal => ("OpGetGlobal", &[2]),
+            Opcode::SetGlobal => ("OpSetGlobal", &[2]),
+            Opcode::Array => ("OpArray", &[2]),
+            Opcode::Hash => ("OpHash", &[2]),
+            Opcode::Index => ("OpIndex", &[]),
+            Opcode::ReturnValue => ("OpReturnValue", &[]),
+            Opcode::Return => ("OpReturn", &[]),
+            Opcode::Call => ("OpCall", &[1]),
+            Opcode::GetLocal => ("OpGetLocal", &[1]),
+            Opcode::SetLocal => ("OpSetLocal", &[1]),
+            Opcode::GetBuiltin => ("OpGetBuiltin", &[1]),
+            Opcode::GetFree => ("OpGetFree", &[1]),
+            // constant index of the compiled function, then the number of free variables on the stack
+            Opcode::Closure => ("OpClosure", &[2, 1]),
+            Opcode::CurrentClosure => ("OpCurrentClosure", &[]),
+        };
+
+        Definition { name, operand_widths }
+    }
+}
+
+// operands wider than their definition allows are truncated; use check_operands first when they
+// aren't known to fit
+pub fn make(op: Opcode, operands: &[usize]) -> Instructions {
+    let definition = op.definition();
+    let length = 1 + definition.operand_widths.iter().sum::<usize>();
+
+    let mut instruction = Vec::with_capacity(length);
+    instruction.push(op as u8);
+
+    for (operand, width) in operands.iter().zip(definition.operand_widths) {
+        match width {
+            2 => instruction.extend_from_slice(&(*operand as u16).to_be_bytes()),
+            1 => instruction.push(*operand as u8),
+            _ => unreachable!("unsupported operand width {}", width),
+        }
+    }
+
+    instruction
+}
+
+pub fn check_operands(op: Opcode, operands: &[usize]) -> Result<(), String> {
+    let definition = op.definition();
+
+    for (operand, width) in operands.iter().zip(definition.operand_widths) {
+        if *operand >= 1 << (8 * width) {
+            return Err(format!("operand {} too large for {}, which allows at most {}", operand, definition.name, (1usize << (8 * width)) - 1));
+        }
+    }
+
+    Ok(())
+}
+
+// decodes the operands following an opcode, returning them with the number of bytes read, or None
+// if instructions ends before the last operand does
+pub fn read_operands(definition: &Definition, instructions: &[u8]) -> Option<(Vec<usize>, usize)> {
+    if instructions.len() < definition.operand_widths.iter().sum() {
+        return None;
+    }
+
+    let mut operands = Vec::with_capacity(definition.operand_widths.len());
+    let mut offset = 0;
+
+    for width in definition.operand_widths {
+        match width {
+            2 => operands.push(read_u16(instructions, offset) as usize),
+            1 => operands.push(instructions[offset] as usize),
+            _ => unreachable!("unsupported operand width {}", width),
+        }
+        offset += width;
+    }
+
+    Some((operands, offset))
+}
+
+pub fn position_at(positions: &[(usize, TokenRange)], offset: usize) -> Option<TokenRange> {
+    let index = positions.partition_point(|(start, _)| *start <= offset);
+    index.checked_sub(1).map(|index| positions[index].1)
+}
+
+pub fn read_u16(instructions: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([instructions[offset], instructions[offset + 1]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_make() {
+        let tests = vec![
+            (Opcode::Constant, vec![65534], vec![Opcode::Constant as u8, 255, 254]),
+            (Opcode::Add, vec![], vec![Opcode::Add as u8]),
+            (Opcode::GetLocal, vec![255], vec![Opcode::GetLocal as u8, 255]),
+            (Opcode::Closure, vec![65534, 255], vec![Opcode::Closure as u8, 255, 254, 255]),
+        ];
+
+        for (op, operands, expected) in tests {
+            assert_eq!(make(op, &operands), expected);
+        }
+    }
+
+    #[test]
+    fn test_check_operands() {
+        assert_eq!(check_operands(Opcode::Constant, &[65535]), Ok(()));
+        assert_eq!(check_operands(Opcode::GetLocal, &[255]), Ok(()));
+        assert_eq!(check_operands(Opcode::Closure, &[65535, 255]), Ok(()));
+        assert_eq!(
+            check_operands(Opcode::Constant, &[65536]),
+            Err("operand 65536 too large for OpConstant, which allows at most 65535".to_string())
+        );
+        assert_eq!(
+            check_operands(Opcode::Closure, &[1, 256]),
+            Err("operand 256 too large for OpClosure, which allows at most 255".to_string())
+        );
+    }
+
+    #[test]
+    fn test_read_operands() {
+        let tests = vec![
+            (Opcode::Constant, vec![65535], 2),
+            (Opcode::Pop, vec![], 0),
+            (Opcode::GetLocal, vec![255], 1),
+            (Opcode::Closure, vec![65535, 255], 3),
+        ];
+
+        for (op, operands, bytes_read) in tests {
+            let instruction = make(op, &operands);
+            let (read, n) = read_operands(&op.definition(), &instruction[1..]).unwrap();
+
+            assert_eq!(n, bytes_read);
+            assert_eq!(read, operands);
+        }
+
+        assert_eq!(read_operands(&Opcode::Closure.definition(), &[0, 1]), None);
+        assert_eq!(read_operands(&Opcode::Constant.definition(), &[]), None);
+    }
+
+    #[test]
+    fn test_position_at() {
+        let first = TokenRange { start: 0, end: 4 };
+        let second = TokenRange { start: 6, end: 9 };
+        let positions = vec![(0, first), (3, second)];
+
+        assert_eq!(position_at(&positions, 0), Some(first));
+        assert_eq!(position_at(&positions, 2), Some(first));
+        assert_eq!(position_at(&positions, 3), Some(second));
+        assert_eq!(position_at(&positions, 100), Some(second));
+        assert_eq!(position_at(&[], 0), None);
+    }
+
+    #[test]
+    fn test_from_byte() {
+        for (byte, op) in OPCODES.iter().enumerate() {
+            assert_eq!(*op as usize, byte);
+            assert_eq!(Opcode::from_byte(byte as u8), Some(*op));
+        }
+        assert_eq!(Opcode::from_byte(OPCODES.len() as u8), None);
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::builtins::builtins::BUILTINS;
+use crate::compiler::compiler::Bytecode;
+use crate::object::object::{CompiledFunction, Object};
+use crate::token::token::TokenRange;
+
+use super::code::{read_operands, Opcode};
+
+// renders a listing of the main program followed by every function it creates, each function
+// indented under the code that references it; source ranges are shown where a position table
+// entry starts, if the bytecode has one. a function is listed once, however many times it's
+// referenced, so a bytecode file with a function that creates itself can still be listed
+pub fn disassemble(bytecode: &Bytecode) -> String {
+    let mut out = String::new();
+    let mut listed = HashSet::new();
+
+    out.push_str("main:\n");
+    disassemble_code(&mut out, &bytecode.instructions, &bytecode.positions, &bytecode.constants, &mut listed, 1);
+
+    out
+}
+
+fn disassemble_code(
+    out: &mut String,
+    instructions: &[u8],
+    positions: &[(usize, TokenRange)],
+    constants: &[Object],
+    listed: &mut HashSet<usize>,
+    depth: usize,
+) {
+    let indent = "    ".repeat(depth);
+    let mut functions = Vec::new();
+    let mut offset = 0;
+
+    while offset < instructions.len() {
+        let op = match Opcode::from_byte(instructions[offset]) {
+            Some(op) => op,
+            None => {
+                writeln!(out, "{}{:04} <unknown opcode {}>", indent, offset, instructions[offset]).unwrap();
+                offset += 1;
+                continue;
+            },
+        };
+        let definition = op.definition();
+        let (operands, read) = match read_operands(&definition, &instructions[offset + 1..]) {
+            Some(operands) => operands,
+            None => {
+                writeln!(out, "{}{:04} {} <truncated>", indent, offset, definition.name).unwrap();
+                break;
+            },
+        };
+
+        let mut line = format!("{:04} {}", offset, definition.name);
+        for operand in &operands {
+            write!(line, " {}", operand).unwrap();
+        }
+
+        let comment = match op {
+            Opcode::Constant => constants.get(operands[0]).map(describe_constant),
+            Opcode::Closure => match constants.get(operands[0]) {
+                Some(Object::CompiledFunction(function)) => {
+                    functions.push((operands[0], operands[1], function));
+                    Some(describe_function(function))
+                },
+                _ => None,
+            },
+            Opcode::GetBuiltin => BUILTINS.get(operands[0]).map(|builtin| builtin.name.to_string()),
+            _ => None,
+        };
+        if let Some(comment) = comment {
+            line = format!("{:<24} ; {}", line, comment);
+        }
+
+        if let Some((_, range)) = positions.iter().find(|(start, _)| *start == offset) {
+            line = format!("{:<48} @{}..{}", line, range.start, range.end);
+        }
+
+        writeln!(out, "{}{}", indent, line).unwrap();
+        offset += 1 + read;
+    }
+
+    for (index, num_free, function) in functions {
+        if !listed.insert(index) {
+            writeln!(out, "{}constant {}: {}, listed above", indent, index, describe_function(function)).unwrap();
+            continue;
+        }
+
+        writeln!(
+            out,
+            "{}constant {}: {}, {} locals, {} free:",
+            indent,
+            index,
+            describe_function(function),
+            function.num_locals,
+            num_free
+        )
+        .unwrap();
+        disassemble_code(out, &function.instructions, &function.positions, constants, listed, depth + 1);
+    }
+}
+
+fn describe_constant(constant: &Object) -> String {
+    match constant {
+        Object::String(value) => format!("{:?}", value),
+        Object::CompiledFunction(function) => describe_function(function),
+        constant => constant.to_string(),
+    }
+}
+
+fn describe_function(function: &CompiledFunction) -> String {
+    format!("fn/{}", function.num_parameters)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::code::code::make;
+    use crate::compiler::compiler::compile_source;
+    use crate::mbc::mbc;
+
+    #[test]
+    fn test_disassemble() {
+        // round trip through a stripped .mbc to drop the position tables
+        let bytecode = compile_source(r#"let add = fn(a) { fn(b) { a + b } }; add(1)(len("ab"))"#, false).unwrap();
+        let bytecode = mbc::decode(&mbc::encode(&bytecode, false).unwrap()).unwrap();
+
+        let expected = "\
+main:
+    0000 OpClosure 1 0       ; fn/1
+    0004 OpSetGlobal 0
+    0007 OpGetGlobal 0
+    0010 OpConstant 2        ; 1
+    0013 OpCall 1
+    0015 OpGetBuiltin 0      ; len
+    0017 OpConstant 3        ; \"ab\"
+    0020 OpCall 1
+    0022 OpCall 1
+    0024 OpPop
+    constant 1: fn/1, 1 locals, 0 free:
+        0000 OpGetLocal 0
+        0002 OpClosure 0 1       ; fn/1
+        0006 OpReturnValue
+        constant 0: fn/1, 1 locals, 1 free:
+            0000 OpGetFree 0
+            0002 OpGetLocal 0
+            0004 OpAdd
+            0005 OpReturnValue
+";
+
+        assert_eq!(disassemble(&bytecode), expected);
+    }
+
+    #[test]
+    fn test_positions() {
+        let expected = "\
+main:
+    0000 OpConstant 0        ; 1                     @0..1
+    0003 OpConstant 1        ; 2                     @4..5
+    0006 OpAdd                                       @0..5
+    0007 OpPop
+";
+
+        assert_eq!(disassemble(&compile_source("1 + 2", false).unwrap()), expected);
+    }
+
+    #[test]
+    fn test_malformed_code() {
+        // a function that creates itself, which the compiler never emits but a .mbc file can hold
+        let function = CompiledFunction {
+            instructions: [make(Opcode::Closure, &[0, 0]), make(Opcode::ReturnValue, &[])].concat(),
+            num_locals: 0,
+            num_parameters: 0,
+            positions: Vec::new(),
+        };
+        let bytecode = Bytecode {
+            instructions: [make(Opcode::Closure, &[0, 0]), vec![Opcode::Constant as u8, 0]].concat(),
+            constants: vec![Object::CompiledFunction(Rc::new(function))],
+            positions: Vec::new(),
+        };
+
+        let expected = "\
+main:
+    0000 OpClosure 0 0       ; fn/0
+    0004 OpConstant <truncated>
+    constant 0: fn/0, 0 locals, 0 free:
+        0000 OpClosure 0 0       ; fn/0
+        0004 OpReturnValue
+        constant 0: fn/0, listed above
+";
+
+        assert_eq!(disassemble(&bytecode), expected);
+    }
+}
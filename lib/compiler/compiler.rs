@@ -0,0 +1,1198 @@
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::builtins::builtins::BUILTINS;
+use crate::code::code::{check_operands, make, Instructions, Opcode, Positions};
+use crate::evaluator::evaluator::{eval_infix_expression, eval_prefix_expression, is_truthy};
+use crate::object::object::{CompiledFunction, Object};
+use crate::parser::ast::{BlockStatement, Expression, Function, Literal, Program, Statement};
+use crate::token::token::{TokenEnum, TokenRange};
+
+use super::symbol_table::{Symbol, SymbolScope, SymbolTable};
+
+pub type CompileError = String;
+
+// local slots and argument counts are single byte operands
+const MAX_LOCALS: usize = u8::MAX as usize + 1;
+const MAX_ARGUMENTS: usize = u8::MAX as usize;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bytecode {
+    pub instructions: Instructions,
+    pub constants: Vec<Object>,
+    pub positions: Positions,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct EmittedInstruction {
+    opcode: Opcode,
+    position: usize,
+}
+
+// the instructions of one function body (or of the main program) while it is being compiled
+#[derive(Default)]
+struct CompilationScope {
+    instructions: Instructions,
+    positions: Positions,
+    last_instruction: Option<EmittedInstruction>,
+    previous_instruction: Option<EmittedInstruction>,
+    // the most recent position a jump was patched to land on
+    jump_target: Option<usize>,
+}
+
+// with optimizations enabled (the default), integer and boolean operations on constants are
+// folded, `if` branches that can't be taken are dropped and values pushed only to be popped again
+// are removed; they can be disabled to see the code as written when debugging
+pub struct Compiler {
+    constants: Vec<Object>,
+    symbol_table: SymbolTable,
+    scopes: Vec<CompilationScope>,
+    // the source of the node being compiled, recorded for every emitted instruction
+    range: Option<TokenRange>,
+    // top-level let names whose statement hasn't been compiled yet
+    pending_globals: HashSet<String>,
+    // the source of the innermost node that failed to compile
+    error_range: Option<TokenRange>,
+    optimize: bool,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        let mut symbol_table = SymbolTable::new();
+        for (index, builtin) in BUILTINS.iter().enumerate() {
+            symbol_table.define_builtin(index, builtin.name);
+        }
+
+        Compiler {
+            constants: Vec::new(),
+            symbol_table,
+            scopes: vec![CompilationScope::default()],
+            range: None,
+            pending_globals: HashSet::new(),
+            error_range: None,
+            optimize: true,
+        }
+    }
+
+    // a compiler for code that runs after earlier code has already bound these names as globals,
+    // such as one more line in a session
+    pub fn new_with_globals(names: &[String]) -> Self {
+        let mut compiler = Self::new();
+        for name in names {
+            compiler.symbol_table.define(name);
+        }
+
+        compiler
+    }
+
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+    }
+
+    // where in the source the error returned by compile was found
+    pub fn error_range(&self) -> Option<TokenRange> {
+        self.error_range
+    }
+
+    // the value of a program is the value of its last statement, like in the evaluator, so a
+    // program that doesn't end in an expression leaves null as the last popped value
+    pub fn compile(&mut self, program: &Program) -> Result<(), CompileError> {
+        self.declare_globals(program);
+        self.compile_statements(&program.statements)?;
+
+        if !matches!(program.statements.last(), None | Some(Statement::Expression(_))) {
+            self.emit(Opcode::Null, &[])?;
+            self.emit(Opcode::Pop, &[])?;
+        }
+
+        Ok(())
+    }
+
+    // globals are resolved when a function is called in the evaluator, so function bodies can refer
+    // to the globals bound further down the program; they get their slots up front
+    fn declare_globals(&mut self, program: &Program) {
+        for statement in &program.statements {
+            if let Statement::LetStatement(let_statement) = statement {
+                let name = &let_statement.identifier.name;
+                if !matches!(self.symbol_table.resolve(name), Some(Symbol { scope: SymbolScope::Global, .. })) {
+                    self.symbol_table.define(name);
+                    self.pending_globals.insert(name.clone());
+                }
+            }
+        }
+    }
+
+    // outside of functions a global only exists once its let statement has run, before that the
+    // name still refers to the builtin it shadows, if any
+    fn resolve(&mut self, name: &str) -> Option<Symbol> {
+        if self.scopes.len() == 1 && self.pending_globals.contains(name) {
+            let index = BUILTINS.iter().position(|builtin| builtin.name == name)?;
+            return Some(Symbol { name: name.to_string(), scope: SymbolScope::Builtin, index });
+        }
+
+        self.symbol_table.resolve(name)
+    }
+
+    pub fn bytecode(&self) -> Bytecode {
+        Bytecode {
+            instructions: self.scope().instructions.clone(),
+            constants: self.constants.clone(),
+            positions: self.scope().positions.clone(),
+        }
+    }
+
+    fn scope(&self) -> &CompilationScope {
+        self.scopes.last().unwrap()
+    }
+
+    fn scope_mut(&mut self) -> &mut CompilationScope {
+        self.scopes.last_mut().unwrap()
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(CompilationScope::default());
+        self.symbol_table = SymbolTable::new_enclosed(std::mem::take(&mut self.symbol_table));
+    }
+
+    fn leave_scope(&mut self) -> CompilationScope {
+        let scope = self.scopes.pop().unwrap();
+        let outer = self.symbol_table.outer.take().unwrap();
+        self.symbol_table = *outer;
+
+        scope
+    }
+
+    fn at<T>(
+        &mut self,
+        range: TokenRange,
+        compile: impl FnOnce(&mut Self) -> Result<T, CompileError>,
+    ) -> Result<T, CompileError> {
+        let outer = self.range.replace(range);
+        let result = compile(self);
+        self.range = outer;
+
+        if result.is_err() && self.error_range.is_none() {
+            self.error_range = Some(range);
+        }
+        result
+    }
+
+    // the value of the last statement is used by the enclosing program, block or function, while
+    // the others are only run for their effects
+    fn compile_statements(&mut self, statements: &[Statement]) -> Result<(), CompileError> {
+        for (index, statement) in statements.iter().enumerate() {
+            self.compile_statement(statement)?;
+            if self.optimize && index + 1 < statements.len() {
+                self.remove_unused_value();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compile_statement(&mut self, statement: &Statement) -> Result<(), CompileError> {
+        self.at(statement.range(), |compiler| compiler.compile_statement_node(statement))
+    }
+
+    fn compile_statement_node(&mut self, statement: &Statement) -> Result<(), CompileError> {
+        match statement {
+            Statement::Expression(expression) => {
+                self.compile_expression(expression)?;
+                self.emit(Opcode::Pop, &[])?;
+            },
+            // the name is defined after compiling the value, so the value still sees any outer
+            // binding it shadows
+            Statement::LetStatement(let_statement) => {
+                match &let_statement.expression {
+                    Expression::Function(function) => self.at(function.range, |compiler| {
+                        compiler.compile_function(function, Some(&let_statement.identifier.name))
+                    })?,
+                    expression => self.compile_expression(expression)?,
+                }
+                let symbol = self.symbol_table.define(&let_statement.identifier.name);
+                if symbol.scope == SymbolScope::Global {
+                    self.pending_globals.remove(&let_statement.identifier.name);
+                }
+                match symbol.scope {
+                    SymbolScope::Global => self.emit(Opcode::SetGlobal, &[symbol.index])?,
+                    _ if symbol.index >= MAX_LOCALS => {
+                        return Err(format!("too many local bindings in function, at most {} are allowed", MAX_LOCALS));
+                    },
+                    _ => self.emit(Opcode::SetLocal, &[symbol.index])?,
+                };
+            },
+            Statement::ReturnStatement(return_statement) => {
+                self.compile_expression(&return_statement.expression)?;
+                self.emit(Opcode::ReturnValue, &[])?;
+            },
+        }
+
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, expression: &Expression) -> Result<(), CompileError> {
+        self.at(expression.range(), |compiler| compiler.compile_expression_node(expression))
+    }
+
+    fn compile_expression_node(&mut self, expression: &Expression) -> Result<(), CompileError> {
+        if self.optimize && matches!(expression, Expression::Prefix(_) | Expression::Infix(_)) {
+            if let Some(value) = constant_value(expression) {
+                self.emit_constant(value)?;
+                return Ok(());
+            }
+        }
+
+        match expression {
+            Expression::Literal(Literal::Integer(integer)) => {
+                let index = self.add_constant(Object::Integer(integer.value));
+                self.emit(Opcode::Constant, &[index])?;
+            },
+            Expression::Literal(Literal::Boolean(boolean)) => {
+                self.emit(if boolean.value { Opcode::True } else { Opcode::False }, &[])?;
+            },
+            Expression::Literal(Literal::String(string)) => {
+                let index = self.add_constant(Object::String(string.value.clone()));
+                self.emit(Opcode::Constant, &[index])?;
+            },
+            Expression::Literal(Literal::Array(array)) => {
+                for element in &array.elements {
+                    self.compile_expression(element)?;
+                }
+                self.emit(Opcode::Array, &[array.elements.len()])?;
+            },
+            Expression::Hash(hash) => {
+                for (key, value) in &hash.pairs {
+                    self.compile_expression(key)?;
+                    self.compile_expression(value)?;
+                }
+                self.emit(Opcode::Hash, &[hash.pairs.len() * 2])?;
+            },
+            Expression::Identifier(identifier) => match self.resolve(&identifier.name) {
+                Some(symbol) => {
+                    self.load_symbol(&symbol)?;
+                },
+                None => return Err(format!("identifier not found: {}", identifier.name)),
+            },
+            Expression::Prefix(prefix) => {
+                self.compile_expression(&prefix.expression)?;
+                match prefix.token.token_type {
+                    TokenEnum::MINUS => self.emit(Opcode::Minus, &[])?,
+                    TokenEnum::BANG => self.emit(Opcode::Bang, &[])?,
+                    ref operator => return Err(format!("unknown operator: {}", operator)),
+                };
+            },
+            Expression::Infix(infix) => {
+                self.compile_expression(&infix.left)?;
+                self.compile_expression(&infix.right)?;
+                let opcode = match infix.token.token_type {
+                    TokenEnum::PLUS => Opcode::Add,
+                    TokenEnum::MINUS => Opcode::Sub,
+                    TokenEnum::ASTERISK => Opcode::Mul,
+                    TokenEnum::SLASH => Opcode::Div,
+                    TokenEnum::GT => Opcode::GreaterThan,
+                    TokenEnum::LT => Opcode::LessThan,
+                    TokenEnum::EQ => Opcode::Equal,
+                    TokenEnum::NEQ => Opcode::NotEqual,
+                    ref operator => return Err(format!("unknown operator: {}", operator)),
+                };
+                self.emit(opcode, &[])?;
+            },
+            Expression::Index(index) => {
+                self.compile_expression(&index.left)?;
+                self.compile_expression(&index.index)?;
+                self.emit(Opcode::Index, &[])?;
+            },
+            Expression::If(if_expression) => {
+                if self.optimize {
+                    if let Some(condition) = constant_value(&if_expression.condition) {
+                        match (is_truthy(&condition), &if_expression.alternative) {
+                            (true, _) => self.compile_block_value(&if_expression.consequence)?,
+                            (false, Some(alternative)) => self.compile_block_value(alternative)?,
+                            (false, None) => {
+                                self.emit(Opcode::Null, &[])?;
+                            },
+                        }
+                        return Ok(());
+                    }
+                }
+
+                self.compile_expression(&if_expression.condition)?;
+
+                // the jump targets are patched once the branches have been compiled
+                let jump_not_truthy = self.emit(Opcode::JumpNotTruthy, &[9999])?;
+                self.compile_block_value(&if_expression.consequence)?;
+                let jump = self.emit(Opcode::Jump, &[9999])?;
+
+                self.patch_jump(jump_not_truthy)?;
+
+                match &if_expression.alternative {
+                    Some(alternative) => self.compile_block_value(alternative)?,
+                    None => {
+                        self.emit(Opcode::Null, &[])?;
+                    },
+                }
+
+                self.patch_jump(jump)?;
+            },
+            Expression::Function(function) => self.compile_function(function, None)?,
+            Expression::Call(call) => {
+                if call.arguments.len() > MAX_ARGUMENTS {
+                    return Err(format!("too many arguments in call, at most {} are allowed", MAX_ARGUMENTS));
+                }
+                self.compile_expression(&call.function)?;
+                for argument in &call.arguments {
+                    self.compile_expression(argument)?;
+                }
+                self.emit(Opcode::Call, &[call.arguments.len()])?;
+            },
+        }
+
+        Ok(())
+    }
+
+    // a function bound by a let statement can call itself through its name
+    fn compile_function(&mut self, function: &Function, name: Option<&str>) -> Result<(), CompileError> {
+        // a call can't pass more arguments than that
+        if function.parameters.len() > MAX_ARGUMENTS {
+            return Err(format!("too many parameters in function, at most {} are allowed", MAX_ARGUMENTS));
+        }
+
+        self.enter_scope();
+
+        if let Some(name) = name {
+            self.symbol_table.define_function_name(name);
+        }
+
+        for parameter in &function.parameters {
+            self.symbol_table.define(&parameter.name);
+        }
+
+        if let Err(error) = self.compile_function_body(function) {
+            self.leave_scope();
+            return Err(error);
+        }
+
+        let free_symbols = self.symbol_table.free_symbols.clone();
+        let num_locals = self.symbol_table.num_definitions;
+        let scope = self.leave_scope();
+
+        // the captured values are pushed in the enclosing scope, where they are still reachable.
+        // a closure keeps the values they had when it was created, so unlike in the evaluator it
+        // doesn't see a local that is redefined afterwards
+        for symbol in &free_symbols {
+            self.load_symbol(symbol)?;
+        }
+
+        let compiled = CompiledFunction {
+            instructions: scope.instructions,
+            num_locals,
+            num_parameters: function.parameters.len(),
+            positions: scope.positions,
+        };
+        let index = self.add_constant(Object::CompiledFunction(Rc::new(compiled)));
+        self.emit(Opcode::Closure, &[index, free_symbols.len()])?;
+
+        Ok(())
+    }
+
+    fn compile_function_body(&mut self, function: &Function) -> Result<(), CompileError> {
+        self.compile_statements(&function.body.statements)?;
+
+        // the value of the last expression is the implicit return value
+        if self.last_instruction_is(Opcode::Pop) {
+            self.remove_last_pop();
+            self.emit(Opcode::ReturnValue, &[])?;
+        }
+        if !self.last_instruction_is(Opcode::ReturnValue) {
+            self.emit(Opcode::Return, &[])?;
+        }
+
+        Ok(())
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol) -> Result<usize, CompileError> {
+        let opcode = match symbol.scope {
+            SymbolScope::Global => Opcode::GetGlobal,
+            SymbolScope::Local => Opcode::GetLocal,
+            SymbolScope::Builtin => Opcode::GetBuiltin,
+            SymbolScope::Free => Opcode::GetFree,
+            SymbolScope::Function => Opcode::CurrentClosure,
+        };
+        self.emit(opcode, &[symbol.index])
+    }
+
+    // compiles a block so that it leaves its value on the stack: the value of its last
+    // expression statement, or null when it doesn't end in one
+    fn compile_block_value(&mut self, block: &BlockStatement) -> Result<(), CompileError> {
+        self.compile_statements(&block.statements)?;
+
+        if matches!(block.statements.last(), Some(Statement::Expression(_))) {
+            self.remove_last_pop();
+        } else {
+            self.emit(Opcode::Null, &[])?;
+        }
+
+        Ok(())
+    }
+
+    fn emit_constant(&mut self, value: Object) -> Result<usize, CompileError> {
+        match value {
+            Object::Boolean(true) => self.emit(Opcode::True, &[]),
+            Object::Boolean(false) => self.emit(Opcode::False, &[]),
+            value => {
+                let index = self.add_constant(value);
+                self.emit(Opcode::Constant, &[index])
+            },
+        }
+    }
+
+    fn add_constant(&mut self, object: Object) -> usize {
+        self.constants.push(object);
+        self.constants.len() - 1
+    }
+
+    // operands too large for their width would be truncated into different, valid looking code,
+    // so the program is rejected instead
+    fn emit(&mut self, opcode: Opcode, operands: &[usize]) -> Result<usize, CompileError> {
+        check_operands(opcode, operands)?;
+        let instruction = make(opcode, operands);
+        let range = self.range;
+        let scope = self.scope_mut();
+        let position = scope.instructions.len();
+        scope.instructions.extend(instruction);
+
+        if let Some(range) = range {
+            if scope.positions.last().map(|(_, last)| *last) != Some(range) {
+                scope.positions.push((position, range));
+            }
+        }
+
+        scope.previous_instruction = scope.last_instruction;
+        scope.last_instruction = Some(EmittedInstruction { opcode, position });
+
+        Ok(position)
+    }
+
+    fn last_instruction_is(&self, opcode: Opcode) -> bool {
+        matches!(self.scope().last_instruction, Some(last) if last.opcode == opcode)
+    }
+
+    fn remove_last_pop(&mut self) {
+        if self.last_instruction_is(Opcode::Pop) {
+            let scope = self.scope_mut();
+            let position = scope.last_instruction.unwrap().position;
+            scope.instructions.truncate(position);
+            scope.positions.retain(|(offset, _)| *offset < position);
+            scope.last_instruction = scope.previous_instruction;
+        }
+    }
+
+    // drops an expression statement whose value is pushed without side effects and then popped;
+    // a jump landing on the pop means the pushed value isn't the only way there
+    fn remove_unused_value(&mut self) {
+        let scope = self.scope_mut();
+        let (Some(last), Some(previous)) = (scope.last_instruction, scope.previous_instruction) else {
+            return;
+        };
+
+        let pure = matches!(
+            previous.opcode,
+            Opcode::Constant
+                | Opcode::True
+                | Opcode::False
+                | Opcode::Null
+                | Opcode::GetGlobal
+                | Opcode::GetLocal
+                | Opcode::GetFree
+                | Opcode::GetBuiltin
+                | Opcode::CurrentClosure
+        );
+        if last.opcode == Opcode::Pop && pure && scope.jump_target != Some(last.position) {
+            scope.instructions.truncate(previous.position);
+            scope.positions.retain(|(offset, _)| *offset < previous.position);
+            scope.last_instruction = None;
+            scope.previous_instruction = None;
+        }
+    }
+
+    fn patch_jump(&mut self, position: usize) -> Result<(), CompileError> {
+        let target = self.scope().instructions.len();
+        self.change_operand(position, target)?;
+        self.scope_mut().jump_target = Some(target);
+
+        Ok(())
+    }
+
+    fn change_operand(&mut self, position: usize, operand: usize) -> Result<(), CompileError> {
+        let instructions = &mut self.scope_mut().instructions;
+        let opcode = Opcode::from_byte(instructions[position]).unwrap();
+        check_operands(opcode, &[operand])?;
+        let instruction = make(opcode, &[operand]);
+
+        instructions[position..position + instruction.len()].copy_from_slice(&instruction);
+
+        Ok(())
+    }
+}
+
+// the value of an expression built only from integer and boolean literals, computed the way the
+// evaluator would; expressions that would fail at runtime are left alone so they still do
+fn constant_value(expression: &Expression) -> Option<Object> {
+    let value = match expression {
+        Expression::Literal(Literal::Integer(integer)) => Object::Integer(integer.value),
+        Expression::Literal(Literal::Boolean(boolean)) => Object::Boolean(boolean.value),
+        Expression::Prefix(prefix) => {
+            eval_prefix_expression(&prefix.token.token_type, constant_value(&prefix.expression)?)
+        },
+        Expression::Infix(infix) => eval_infix_expression(
+            &infix.token.token_type,
+            constant_value(&infix.left)?,
+            constant_value(&infix.right)?,
+        ),
+        _ => return None,
+    };
+
+    match value {
+        Object::Integer(_) | Object::Boolean(_) => Some(value),
+        _ => None,
+    }
+}
+
+// parses and compiles input, for the tests of the compiler and of the modules that run its output
+#[cfg(test)]
+pub(crate) fn compile_source(input: &str, optimize: bool) -> Result<Bytecode, CompileError> {
+    use crate::lexer::lexer::Lexer;
+    use crate::parser::parser::Parser;
+
+    let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+
+    let mut compiler = Compiler::new();
+    compiler.set_optimize(optimize);
+    compiler.compile(&program)?;
+    Ok(compiler.bytecode())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lexer::Lexer;
+    use crate::parser::parser::Parser;
+
+    // optimizations are off unless a test is about them, so the code matches the source
+    fn compile(input: &str) -> Result<Bytecode, CompileError> {
+        compile_source(input, false)
+    }
+
+    // positions are checked separately, in test_positions
+    fn test_compile(input: &str, constants: Vec<Object>, instructions: Vec<Instructions>) {
+        test_compile_with(input, false, constants, instructions);
+    }
+
+    fn test_compile_with(input: &str, optimize: bool, constants: Vec<Object>, instructions: Vec<Instructions>) {
+        let bytecode = compile_source(input, optimize).unwrap();
+        let compiled_constants: Vec<Object> = bytecode.constants.into_iter().map(without_positions).collect();
+
+        assert_eq!(bytecode.instructions, instructions.concat(), "{}", input);
+        assert_eq!(compiled_constants, constants, "{}", input);
+    }
+
+    fn without_positions(constant: Object) -> Object {
+        match constant {
+            Object::CompiledFunction(function) => Object::CompiledFunction(Rc::new(CompiledFunction {
+                positions: Vec::new(),
+                ..(*function).clone()
+            })),
+            constant => constant,
+        }
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        test_compile(
+            "1 + 2",
+            vec![Object::Integer(1), Object::Integer(2)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Add, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+        test_compile(
+            "1; 2 * 3",
+            vec![Object::Integer(1), Object::Integer(2), Object::Integer(3)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Mul, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+        test_compile(
+            "-1",
+            vec![Object::Integer(1)],
+            vec![make(Opcode::Constant, &[0]), make(Opcode::Minus, &[]), make(Opcode::Pop, &[])],
+        );
+    }
+
+    #[test]
+    fn test_boolean_expressions() {
+        test_compile(
+            "1 < 2 == !false",
+            vec![Object::Integer(1), Object::Integer(2)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::LessThan, &[]),
+                make(Opcode::False, &[]),
+                make(Opcode::Bang, &[]),
+                make(Opcode::Equal, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_conditionals() {
+        test_compile(
+            "if (true) { 10 }; 3333;",
+            vec![Object::Integer(10), Object::Integer(3333)],
+            vec![
+                // 0000
+                make(Opcode::True, &[]),
+                // 0001
+                make(Opcode::JumpNotTruthy, &[10]),
+                // 0004
+                make(Opcode::Constant, &[0]),
+                // 0007
+                make(Opcode::Jump, &[11]),
+                // 0010
+                make(Opcode::Null, &[]),
+                // 0011
+                make(Opcode::Pop, &[]),
+                // 0012
+                make(Opcode::Constant, &[1]),
+                // 0015
+                make(Opcode::Pop, &[]),
+            ],
+        );
+        test_compile(
+            "if (true) { 10 } else { 20 }",
+            vec![Object::Integer(10), Object::Integer(20)],
+            vec![
+                // 0000
+                make(Opcode::True, &[]),
+                // 0001
+                make(Opcode::JumpNotTruthy, &[10]),
+                // 0004
+                make(Opcode::Constant, &[0]),
+                // 0007
+                make(Opcode::Jump, &[13]),
+                // 0010
+                make(Opcode::Constant, &[1]),
+                // 0013
+                make(Opcode::Pop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_global_let_statements() {
+        test_compile(
+            "let one = 1; let two = one; two;",
+            vec![Object::Integer(1)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::SetGlobal, &[1]),
+                make(Opcode::GetGlobal, &[1]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+        test_compile(
+            "let one = 1;",
+            vec![Object::Integer(1)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_collections() {
+        test_compile(
+            r#"["a", 1][1]"#,
+            vec![Object::String("a".to_string()), Object::Integer(1), Object::Integer(1)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Array, &[2]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Index, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+        test_compile(
+            "{1: 2 + 3}",
+            vec![Object::Integer(1), Object::Integer(2), Object::Integer(3)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Add, &[]),
+                make(Opcode::Hash, &[2]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+    }
+
+    fn function(instructions: Vec<Instructions>, num_locals: usize, num_parameters: usize) -> Object {
+        Object::CompiledFunction(Rc::new(CompiledFunction {
+            instructions: instructions.concat(),
+            num_locals,
+            num_parameters,
+            positions: Vec::new(),
+        }))
+    }
+
+    #[test]
+    fn test_functions() {
+        test_compile(
+            "fn() { return 5 + 10 }",
+            vec![
+                Object::Integer(5),
+                Object::Integer(10),
+                function(
+                    vec![
+                        make(Opcode::Constant, &[0]),
+                        make(Opcode::Constant, &[1]),
+                        make(Opcode::Add, &[]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    0,
+                    0,
+                ),
+            ],
+            vec![make(Opcode::Closure, &[2, 0]), make(Opcode::Pop, &[])],
+        );
+        test_compile(
+            "fn() { 1; 2 }",
+            vec![
+                Object::Integer(1),
+                Object::Integer(2),
+                function(
+                    vec![
+                        make(Opcode::Constant, &[0]),
+                        make(Opcode::Pop, &[]),
+                        make(Opcode::Constant, &[1]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    0,
+                    0,
+                ),
+            ],
+            vec![make(Opcode::Closure, &[2, 0]), make(Opcode::Pop, &[])],
+        );
+        test_compile(
+            "fn() { }",
+            vec![function(vec![make(Opcode::Return, &[])], 0, 0)],
+            vec![make(Opcode::Closure, &[0, 0]), make(Opcode::Pop, &[])],
+        );
+    }
+
+    #[test]
+    fn test_calls() {
+        test_compile(
+            "let f = fn(a, b) { a }; f(1, 2);",
+            vec![
+                function(vec![make(Opcode::GetLocal, &[0]), make(Opcode::ReturnValue, &[])], 2, 2),
+                Object::Integer(1),
+                Object::Integer(2),
+            ],
+            vec![
+                make(Opcode::Closure, &[0, 0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Call, &[2]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_local_let_statements() {
+        test_compile(
+            "let g = 1; fn() { let a = g; a }",
+            vec![
+                Object::Integer(1),
+                function(
+                    vec![
+                        make(Opcode::GetGlobal, &[0]),
+                        make(Opcode::SetLocal, &[0]),
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    0,
+                ),
+            ],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Closure, &[1, 0]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_builtins() {
+        test_compile(
+            "len([]); push([], 1);",
+            vec![Object::Integer(1)],
+            vec![
+                make(Opcode::GetBuiltin, &[0]),
+                make(Opcode::Array, &[0]),
+                make(Opcode::Call, &[1]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::GetBuiltin, &[5]),
+                make(Opcode::Array, &[0]),
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Call, &[2]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+        test_compile(
+            "fn() { len }",
+            vec![function(vec![make(Opcode::GetBuiltin, &[0]), make(Opcode::ReturnValue, &[])], 0, 0)],
+            vec![make(Opcode::Closure, &[0, 0]), make(Opcode::Pop, &[])],
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        test_compile(
+            "fn(a) { fn(b) { fn(c) { a + b + c } } }",
+            vec![
+                function(
+                    vec![
+                        make(Opcode::GetFree, &[0]),
+                        make(Opcode::GetFree, &[1]),
+                        make(Opcode::Add, &[]),
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::Add, &[]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    1,
+                ),
+                function(
+                    vec![
+                        make(Opcode::GetFree, &[0]),
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::Closure, &[0, 2]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    1,
+                ),
+                function(
+                    vec![
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::Closure, &[1, 1]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    1,
+                ),
+            ],
+            vec![make(Opcode::Closure, &[2, 0]), make(Opcode::Pop, &[])],
+        );
+    }
+
+    #[test]
+    fn test_recursive_functions() {
+        test_compile(
+            "let countdown = fn(x) { countdown(x - 1) };",
+            vec![
+                Object::Integer(1),
+                function(
+                    vec![
+                        make(Opcode::CurrentClosure, &[]),
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::Constant, &[0]),
+                        make(Opcode::Sub, &[]),
+                        make(Opcode::Call, &[1]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    1,
+                ),
+            ],
+            vec![
+                make(Opcode::Closure, &[1, 0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+        test_compile(
+            "fn() { let inner = fn() { inner() }; inner }",
+            vec![
+                function(vec![make(Opcode::CurrentClosure, &[]), make(Opcode::Call, &[0]), make(Opcode::ReturnValue, &[])], 0, 0),
+                function(
+                    vec![
+                        make(Opcode::Closure, &[0, 0]),
+                        make(Opcode::SetLocal, &[0]),
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    0,
+                ),
+            ],
+            vec![make(Opcode::Closure, &[1, 0]), make(Opcode::Pop, &[])],
+        );
+    }
+
+    #[test]
+    fn test_positions() {
+        let range = |start, end| TokenRange { start, end };
+
+        let bytecode = compile("1 + 2; let x = -3;").unwrap();
+        assert_eq!(
+            bytecode.positions,
+            vec![
+                (0, range(0, 1)),
+                (3, range(4, 5)),
+                (6, range(0, 5)),
+                (8, range(16, 17)),
+                (11, range(15, 17)),
+                (12, range(7, 18)),
+            ]
+        );
+
+        let bytecode = compile("let f = fn() { 2 };").unwrap();
+        assert_eq!(bytecode.positions, vec![(0, range(8, 18)), (4, range(0, 19))]);
+        match &bytecode.constants[1] {
+            Object::CompiledFunction(function) => assert_eq!(function.positions, vec![(0, range(15, 16)), (3, range(8, 18))]),
+            constant => panic!("expected a compiled function, got {:?}", constant),
+        }
+    }
+
+    #[test]
+    fn test_constant_folding() {
+        test_compile_with(
+            "60 * 60 * 24",
+            true,
+            vec![Object::Integer(86400)],
+            vec![make(Opcode::Constant, &[0]), make(Opcode::Pop, &[])],
+        );
+        test_compile_with(
+            "let x = -(2 + 3) * 4; !(x > 1 == true)",
+            true,
+            vec![Object::Integer(-20), Object::Integer(1)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::GreaterThan, &[]),
+                make(Opcode::True, &[]),
+                make(Opcode::Equal, &[]),
+                make(Opcode::Bang, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+        // left for the VM to report
+        test_compile_with(
+            "1 / 0",
+            true,
+            vec![Object::Integer(1), Object::Integer(0)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Div, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_dead_branches() {
+        test_compile_with(
+            "if (1 < 2) { 10 } else { 20 }",
+            true,
+            vec![Object::Integer(10)],
+            vec![make(Opcode::Constant, &[0]), make(Opcode::Pop, &[])],
+        );
+        test_compile_with(
+            "if (false) { 10 } else { 20 }",
+            true,
+            vec![Object::Integer(20)],
+            vec![make(Opcode::Constant, &[0]), make(Opcode::Pop, &[])],
+        );
+        test_compile_with("if (!true) { 30 }", true, vec![], vec![make(Opcode::Null, &[]), make(Opcode::Pop, &[])]);
+    }
+
+    #[test]
+    fn test_unused_values() {
+        test_compile_with(
+            "let a = 1; a; 2; len; a",
+            true,
+            vec![Object::Integer(1), Object::Integer(2)],
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+        test_compile_with(
+            "fn(a) { a; 1 }",
+            true,
+            vec![
+                Object::Integer(1),
+                function(vec![make(Opcode::Constant, &[0]), make(Opcode::ReturnValue, &[])], 1, 1),
+            ],
+            vec![make(Opcode::Closure, &[1, 0]), make(Opcode::Pop, &[])],
+        );
+        // the jump from the consequence lands on the pop, so the alternative's value is kept
+        test_compile_with(
+            "let c = true; if (c) { 1 } else { 2 }; 3",
+            true,
+            vec![Object::Integer(1), Object::Integer(2), Object::Integer(3)],
+            vec![
+                make(Opcode::True, &[]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::JumpNotTruthy, &[16]),
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Jump, &[19]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_new_with_globals() {
+        let program = Parser::new(Lexer::new("b + a")).parse_program().unwrap();
+        let mut compiler = Compiler::new_with_globals(&["a".to_string(), "b".to_string()]);
+        compiler.compile(&program).unwrap();
+
+        assert_eq!(
+            compiler.bytecode().instructions,
+            [
+                make(Opcode::GetGlobal, &[1]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Add, &[]),
+                make(Opcode::Pop, &[]),
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        assert_eq!(compile("x + 1"), Err("identifier not found: x".to_string()));
+        assert_eq!(compile("fn() { y }"), Err("identifier not found: y".to_string()));
+        assert_eq!(compile("let f = 1 + f;"), Err("identifier not found: f".to_string()));
+        assert_eq!(compile("g(); let g = fn() { 1 };"), Err("identifier not found: g".to_string()));
+
+        // the innermost node that failed is reported
+        let program = Parser::new(Lexer::new("let a = 1;\nlet b = [a, c];")).parse_program().unwrap();
+        let mut compiler = Compiler::new();
+        assert!(compiler.compile(&program).is_err());
+        assert_eq!(compiler.error_range(), Some(TokenRange { start: 23, end: 24 }));
+    }
+
+    #[test]
+    fn test_forward_references() {
+        test_compile(
+            "let f = fn() { g }; let g = 1;",
+            vec![function(vec![make(Opcode::GetGlobal, &[1]), make(Opcode::ReturnValue, &[])], 0, 0), Object::Integer(1)],
+            vec![
+                make(Opcode::Closure, &[0, 0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::SetGlobal, &[1]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+        // before its let statement a global shadowing a builtin is still the builtin at the top level
+        test_compile(
+            "len; let len = 1;",
+            vec![Object::Integer(1)],
+            vec![
+                make(Opcode::GetBuiltin, &[0]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Pop, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_operand_limits() {
+        let elements = vec!["true"; 65536].join(", ");
+        assert_eq!(
+            compile(&format!("[{}]", elements)),
+            Err("operand 65536 too large for OpArray, which allows at most 65535".to_string())
+        );
+
+        let constants: Vec<String> = (0..65537).map(|n| n.to_string()).collect();
+        assert_eq!(
+            compile(&constants.join("; ")),
+            Err("operand 65536 too large for OpConstant, which allows at most 65535".to_string())
+        );
+    }
+
+    #[test]
+    fn test_function_limits() {
+        let lets = |n: usize| (0..n).map(|i| format!("let v{} = {};", i, i)).collect::<Vec<String>>().join(" ");
+        let arguments = |n: usize| vec!["1"; n].join(", ");
+        let parameters = |n: usize| (0..n).map(|i| format!("p{}", i)).collect::<Vec<String>>().join(", ");
+
+        assert!(compile(&format!("fn() {{ {} v0 }}", lets(256))).is_ok());
+        assert_eq!(
+            compile(&format!("fn() {{ {} v0 }}", lets(300))),
+            Err("too many local bindings in function, at most 256 are allowed".to_string())
+        );
+        assert_eq!(
+            compile(&format!("fn(a) {{ {} a }}", lets(256))),
+            Err("too many local bindings in function, at most 256 are allowed".to_string())
+        );
+
+        assert!(compile(&format!("len({})", arguments(255))).is_ok());
+        assert_eq!(
+            compile(&format!("len({})", arguments(256))),
+            Err("too many arguments in call, at most 255 are allowed".to_string())
+        );
+
+        assert!(compile(&format!("fn({}) {{ p254 }}", parameters(255))).is_ok());
+        assert_eq!(
+            compile(&format!("fn({}) {{ 1 }}", parameters(256))),
+            Err("too many parameters in function, at most 255 are allowed".to_string())
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolScope {
+    Global,
+    Local,
+    Builtin,
+    Free,
+    Function,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub scope: SymbolScope,
+    pub index: usize,
+}
+
+// one table per function being compiled, chained to the table of the enclosing function; the
+// outermost table holds globals and builtins
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    pub outer: Option<Box<SymbolTable>>,
+    store: HashMap<String, Symbol>,
+    pub num_definitions: usize,
+    pub free_symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        SymbolTable::default()
+    }
+
+    pub fn new_enclosed(outer: SymbolTable) -> Self {
+        SymbolTable {
+            outer: Some(Box::new(outer)),
+            ..SymbolTable::default()
+        }
+    }
+
+    // redefining a name in the same scope reuses its slot
+    pub fn define(&mut self, name: &str) -> Symbol {
+        let scope = if self.outer.is_some() { SymbolScope::Local } else { SymbolScope::Global };
+
+        if let Some(existing) = self.store.get(name) {
+            if existing.scope == scope {
+                return existing.clone();
+            }
+        }
+
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope,
+            index: self.num_definitions,
+        };
+        self.store.insert(name.to_string(), symbol.clone());
+        self.num_definitions += 1;
+
+        symbol
+    }
+
+    pub fn define_builtin(&mut self, index: usize, name: &str) -> Symbol {
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Builtin,
+            index,
+        };
+        self.store.insert(name.to_string(), symbol.clone());
+
+        symbol
+    }
+
+    // the name a function literal is bound to, so the function can refer to itself
+    pub fn define_function_name(&mut self, name: &str) -> Symbol {
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Function,
+            index: 0,
+        };
+        self.store.insert(name.to_string(), symbol.clone());
+
+        symbol
+    }
+
+    fn define_free(&mut self, original: Symbol) -> Symbol {
+        let symbol = Symbol {
+            name: original.name.clone(),
+            scope: SymbolScope::Free,
+            index: self.free_symbols.len(),
+        };
+        self.free_symbols.push(original);
+        self.store.insert(symbol.name.clone(), symbol.clone());
+
+        symbol
+    }
+
+    // a local of an enclosing function is captured as a free variable of every function between
+    // it and this one
+    pub fn resolve(&mut self, name: &str) -> Option<Symbol> {
+        if let Some(symbol) = self.store.get(name) {
+            return Some(symbol.clone());
+        }
+
+        let symbol = self.outer.as_mut()?.resolve(name)?;
+        match symbol.scope {
+            SymbolScope::Global | SymbolScope::Builtin => Some(symbol),
+            SymbolScope::Local | SymbolScope::Free | SymbolScope::Function => Some(self.define_free(symbol)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(name: &str, scope: SymbolScope, index: usize) -> Symbol {
+        Symbol { name: name.to_string(), scope, index }
+    }
+
+    #[test]
+    fn test_define_and_resolve() {
+        let mut global = SymbolTable::new();
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+        assert_eq!(global.define("b"), symbol("b", SymbolScope::Global, 1));
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+
+        let mut local = SymbolTable::new_enclosed(global);
+        assert_eq!(local.define("c"), symbol("c", SymbolScope::Local, 0));
+        assert_eq!(local.define("a"), symbol("a", SymbolScope::Local, 1));
+
+        assert_eq!(local.resolve("a"), Some(symbol("a", SymbolScope::Local, 1)));
+        assert_eq!(local.resolve("b"), Some(symbol("b", SymbolScope::Global, 1)));
+        assert_eq!(local.resolve("c"), Some(symbol("c", SymbolScope::Local, 0)));
+        assert_eq!(local.resolve("d"), None);
+    }
+
+    #[test]
+    fn test_builtins() {
+        let mut global = SymbolTable::new();
+        global.define_builtin(0, "len");
+        global.define_builtin(5, "push");
+
+        let mut local = SymbolTable::new_enclosed(SymbolTable::new_enclosed(global));
+        assert_eq!(local.resolve("len"), Some(symbol("len", SymbolScope::Builtin, 0)));
+        assert_eq!(local.resolve("push"), Some(symbol("push", SymbolScope::Builtin, 5)));
+        assert!(local.free_symbols.is_empty());
+    }
+
+    #[test]
+    fn test_shadowing_builtins() {
+        let mut global = SymbolTable::new();
+        global.define_builtin(0, "len");
+
+        assert_eq!(global.define("len"), symbol("len", SymbolScope::Global, 0));
+        assert_eq!(global.resolve("len"), Some(symbol("len", SymbolScope::Global, 0)));
+    }
+
+    #[test]
+    fn test_resolve_free() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+
+        let mut first = SymbolTable::new_enclosed(global);
+        first.define("c");
+
+        let mut second = SymbolTable::new_enclosed(first);
+        second.define("e");
+
+        assert_eq!(second.resolve("a"), Some(symbol("a", SymbolScope::Global, 0)));
+        assert_eq!(second.resolve("c"), Some(symbol("c", SymbolScope::Free, 0)));
+        assert_eq!(second.resolve("e"), Some(symbol("e", SymbolScope::Local, 0)));
+        assert_eq!(second.free_symbols, vec![symbol("c", SymbolScope::Local, 0)]);
+
+        let mut third = SymbolTable::new_enclosed(second);
+        assert_eq!(third.resolve("c"), Some(symbol("c", SymbolScope::Free, 0)));
+        assert_eq!(third.resolve("e"), Some(symbol("e", SymbolScope::Free, 1)));
+        assert_eq!(
+            third.free_symbols,
+            vec![symbol("c", SymbolScope::Free, 0), symbol("e", SymbolScope::Local, 0)]
+        );
+        assert_eq!(third.resolve("unknown"), None);
+    }
+
+    #[test]
+    fn test_function_names() {
+        let mut outer = SymbolTable::new_enclosed(SymbolTable::new());
+        outer.define_function_name("f");
+        assert_eq!(outer.resolve("f"), Some(symbol("f", SymbolScope::Function, 0)));
+
+        let mut inner = SymbolTable::new_enclosed(outer);
+        assert_eq!(inner.resolve("f"), Some(symbol("f", SymbolScope::Free, 0)));
+        assert_eq!(inner.define("f"), symbol("f", SymbolScope::Local, 0));
+
+        let mut shadowed = SymbolTable::new_enclosed(SymbolTable::new());
+        shadowed.define_function_name("f");
+        assert_eq!(shadowed.define("f"), symbol("f", SymbolScope::Local, 0));
+        assert_eq!(shadowed.resolve("f"), Some(symbol("f", SymbolScope::Local, 0)));
+    }
+}
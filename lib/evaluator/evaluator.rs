@@ -0,0 +1,721 @@
+use std::{cell::{Cell, RefCell}, collections::BTreeMap, rc::Rc};
+
+use crate::builtins::builtins;
+use crate::object::environment::{Env, Environment};
+use crate::object::object::{Function, Object};
+use crate::parser::ast::{Program, Statement, Expression, Literal, BlockStatement, Hash};
+use crate::token::token::TokenEnum;
+
+// every call nests on the Rust stack, so calls fail with a stack overflow error once they use this
+// much of it, which leaves room to spare on the 2 MiB stacks spawned threads get by default
+const DEFAULT_STACK_LIMIT: usize = 1536 * 1024;
+
+thread_local! {
+    static STACK_LIMIT: Cell<usize> = const { Cell::new(DEFAULT_STACK_LIMIT) };
+    // where the stack was when the outermost call started, zero outside of calls
+    static STACK_BASE: Cell<usize> = const { Cell::new(0) };
+}
+
+// sets how many bytes of stack function calls may use on the current thread; threads with a
+// larger stack can allow deeper recursion
+pub fn set_stack_limit(bytes: usize) {
+    STACK_LIMIT.set(bytes);
+}
+
+pub fn eval_program(program: &Program, env: &Env) -> Object {
+    let mut result = Object::Null;
+
+    for statement in &program.statements {
+        result = eval_statement(statement, env);
+
+        match result {
+            Object::ReturnValue(value) => return *value,
+            Object::Error(_) => return result,
+            _ => {},
+        }
+    }
+
+    result
+}
+
+// unlike eval_program, a block leaves return values wrapped so they unwind through enclosing blocks
+fn eval_block_statement(block: &BlockStatement, env: &Env) -> Object {
+    let mut result = Object::Null;
+
+    for statement in &block.statements {
+        result = eval_statement(statement, env);
+
+        if matches!(result, Object::ReturnValue(_) | Object::Error(_)) {
+            return result;
+        }
+    }
+
+    result
+}
+
+fn eval_statement(statement: &Statement, env: &Env) -> Object {
+    match statement {
+        Statement::Expression(expression) => eval_expression(expression, env),
+        Statement::ReturnStatement(return_statement) => {
+            let value = eval_expression(&return_statement.expression, env);
+            if matches!(value, Object::ReturnValue(_) | Object::Error(_)) {
+                return value;
+            }
+            Object::ReturnValue(Box::new(value))
+        },
+        Statement::LetStatement(let_statement) => {
+            let value = eval_expression(&let_statement.expression, env);
+            if value.is_error() {
+                return value;
+            }
+            env.borrow_mut().set(&let_statement.identifier.name, value);
+            Object::Null
+        },
+    }
+}
+
+fn eval_expression(expression: &Expression, env: &Env) -> Object {
+    match expression {
+        Expression::Literal(Literal::Integer(integer)) => Object::Integer(integer.value),
+        Expression::Literal(Literal::Boolean(boolean)) => Object::Boolean(boolean.value),
+        Expression::Literal(Literal::String(string)) => Object::String(string.value.clone()),
+        Expression::Literal(Literal::Array(array)) => match eval_expressions(&array.elements, env) {
+            Ok(elements) => Object::Array(elements),
+            Err(error) => error,
+        },
+        Expression::Identifier(identifier) => eval_identifier(&identifier.name, env),
+        Expression::Prefix(prefix) => {
+            let right = eval_expression(&prefix.expression, env);
+            if right.is_error() {
+                return right;
+            }
+            eval_prefix_expression(&prefix.token.token_type, right)
+        },
+        Expression::Infix(infix) => {
+            let left = eval_expression(&infix.left, env);
+            if left.is_error() {
+                return left;
+            }
+            let right = eval_expression(&infix.right, env);
+            if right.is_error() {
+                return right;
+            }
+            eval_infix_expression(&infix.token.token_type, left, right)
+        },
+        Expression::If(if_expression) => {
+            let condition = eval_expression(&if_expression.condition, env);
+            if condition.is_error() {
+                return condition;
+            }
+
+            if is_truthy(&condition) {
+                eval_block_statement(&if_expression.consequence, env)
+            } else if let Some(alternative) = &if_expression.alternative {
+                eval_block_statement(alternative, env)
+            } else {
+                Object::Null
+            }
+        },
+        Expression::Function(function) => Object::Function(Rc::new(Function {
+            parameters: function.parameters.clone(),
+            body: function.body.clone(),
+            env: Rc::clone(env),
+        })),
+        Expression::Call(call) => {
+            let function = eval_expression(&call.function, env);
+            if function.is_error() {
+                return function;
+            }
+
+            match eval_expressions(&call.arguments, env) {
+                Ok(arguments) => apply_function(function, arguments),
+                Err(error) => error,
+            }
+        },
+        Expression::Index(index) => {
+            let left = eval_expression(&index.left, env);
+            if left.is_error() {
+                return left;
+            }
+            let index = eval_expression(&index.index, env);
+            if index.is_error() {
+                return index;
+            }
+            eval_index_expression(left, index)
+        },
+        Expression::Hash(hash) => eval_hash_literal(hash, env),
+    }
+}
+
+fn eval_hash_literal(hash: &Hash, env: &Env) -> Object {
+    let mut pairs = BTreeMap::new();
+
+    for (key_expression, value_expression) in &hash.pairs {
+        let key = eval_expression(key_expression, env);
+        if key.is_error() {
+            return key;
+        }
+
+        let Some(hash_key) = key.hash_key() else {
+            return Object::Error(format!("unusable as hash key: {}", key.type_name()));
+        };
+
+        let value = eval_expression(value_expression, env);
+        if value.is_error() {
+            return value;
+        }
+
+        pairs.insert(hash_key, value);
+    }
+
+    Object::Hash(pairs)
+}
+
+// evaluates left to right, stopping at the first error
+fn eval_expressions(expressions: &[Expression], env: &Env) -> Result<Vec<Object>, Object> {
+    let mut values = Vec::with_capacity(expressions.len());
+
+    for expression in expressions {
+        let value = eval_expression(expression, env);
+        if value.is_error() {
+            return Err(value);
+        }
+        values.push(value);
+    }
+
+    Ok(values)
+}
+
+pub(crate) fn eval_index_expression(left: Object, index: Object) -> Object {
+    match (&left, &index) {
+        (Object::Array(elements), Object::Integer(i)) => usize::try_from(*i)
+            .ok()
+            .and_then(|i| elements.get(i))
+            .cloned()
+            .unwrap_or(Object::Null),
+        (Object::Hash(pairs), _) => match index.hash_key() {
+            Some(key) => pairs.get(&key).cloned().unwrap_or(Object::Null),
+            None => Object::Error(format!("unusable as hash key: {}", index.type_name())),
+        },
+        _ => Object::Error(format!("index operator not supported: {}", left.type_name())),
+    }
+}
+
+fn apply_function(function: Object, arguments: Vec<Object>) -> Object {
+    match function {
+        Object::Function(function) => {
+            if function.parameters.len() != arguments.len() {
+                return Object::Error(format!(
+                    "wrong number of arguments: want={}, got={}",
+                    function.parameters.len(),
+                    arguments.len()
+                ));
+            }
+
+            let mut extended = Environment::new_enclosed(Rc::clone(&function.env));
+            for (parameter, argument) in function.parameters.iter().zip(arguments) {
+                extended.set(&parameter.name, argument);
+            }
+
+            // the stack grows down, so the calls in progress use the distance to where the
+            // outermost one started
+            let marker = 0u8;
+            let here = std::ptr::addr_of!(marker) as usize;
+            let base = STACK_BASE.get();
+            if base == 0 {
+                STACK_BASE.set(here);
+            } else if base.saturating_sub(here) > STACK_LIMIT.get() {
+                return Object::Error("stack overflow".to_string());
+            }
+
+            let result = eval_block_statement(&function.body, &Rc::new(RefCell::new(extended)));
+            if base == 0 {
+                STACK_BASE.set(0);
+            }
+
+            match result {
+                Object::ReturnValue(value) => *value,
+                result => result,
+            }
+        },
+        Object::Builtin(builtin) => (builtin.function)(arguments),
+        Object::HostFunction(host) => (host.function)(arguments),
+        _ => Object::Error(format!("not a function: {}", function.type_name())),
+    }
+}
+
+fn eval_identifier(name: &str, env: &Env) -> Object {
+    if let Some(value) = env.borrow().get(name) {
+        return value;
+    }
+
+    match builtins::lookup(name) {
+        Some(builtin) => builtin,
+        None => Object::Error(format!("identifier not found: {}", name)),
+    }
+}
+
+pub(crate) fn eval_prefix_expression(operator: &TokenEnum, right: Object) -> Object {
+    match operator {
+        TokenEnum::BANG => Object::Boolean(!is_truthy(&right)),
+        TokenEnum::MINUS => match right {
+            Object::Integer(value) => Object::Integer(value.wrapping_neg()),
+            _ => Object::Error(format!("unknown operator: -{}", right.type_name())),
+        },
+        _ => Object::Error(format!("unknown operator: {}{}", operator, right.type_name())),
+    }
+}
+
+pub(crate) fn eval_infix_expression(operator: &TokenEnum, left: Object, right: Object) -> Object {
+    match (&left, &right) {
+        (Object::Integer(left), Object::Integer(right)) => eval_integer_infix_expression(operator, *left, *right),
+        (Object::String(l), Object::String(r)) => match operator {
+            TokenEnum::PLUS => Object::String(format!("{}{}", l, r)),
+            TokenEnum::EQ => Object::Boolean(l == r),
+            TokenEnum::NEQ => Object::Boolean(l != r),
+            _ => unknown_infix_operator(operator, &left, &right),
+        },
+        (Object::Boolean(l), Object::Boolean(r)) => match operator {
+            TokenEnum::EQ => Object::Boolean(l == r),
+            TokenEnum::NEQ => Object::Boolean(l != r),
+            _ => unknown_infix_operator(operator, &left, &right),
+        },
+        _ if left.type_name() != right.type_name() => Object::Error(format!(
+            "type mismatch: {} {} {}",
+            left.type_name(),
+            operator,
+            right.type_name()
+        )),
+        _ => unknown_infix_operator(operator, &left, &right),
+    }
+}
+
+fn unknown_infix_operator(operator: &TokenEnum, left: &Object, right: &Object) -> Object {
+    Object::Error(format!(
+        "unknown operator: {} {} {}",
+        left.type_name(),
+        operator,
+        right.type_name()
+    ))
+}
+
+fn eval_integer_infix_expression(operator: &TokenEnum, left: i64, right: i64) -> Object {
+    match operator {
+        TokenEnum::PLUS => Object::Integer(left.wrapping_add(right)),
+        TokenEnum::MINUS => Object::Integer(left.wrapping_sub(right)),
+        TokenEnum::ASTERISK => Object::Integer(left.wrapping_mul(right)),
+        TokenEnum::SLASH => {
+            if right == 0 {
+                Object::Error("division by zero".to_string())
+            } else {
+                Object::Integer(left.wrapping_div(right))
+            }
+        },
+        TokenEnum::LT => Object::Boolean(left < right),
+        TokenEnum::GT => Object::Boolean(left > right),
+        TokenEnum::EQ => Object::Boolean(left == right),
+        TokenEnum::NEQ => Object::Boolean(left != right),
+        _ => Object::Error(format!("unknown operator: INTEGER {} INTEGER", operator)),
+    }
+}
+
+pub(crate) fn is_truthy(object: &Object) -> bool {
+    !matches!(object, Object::Null | Object::Boolean(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lexer::Lexer;
+    use crate::parser::ast::{Infix, Integer, Prefix};
+    use crate::parser::parser::Parser;
+    use crate::token::token::{Token, TokenRange};
+
+    const RANGE: TokenRange = TokenRange { start: 0, end: 0 };
+
+    fn test_eval(input: &str) -> Object {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program().unwrap();
+
+        eval_program(&program, &Rc::new(RefCell::new(Environment::new())))
+    }
+
+    fn test_eval_expression(expression: &Expression) -> Object {
+        eval_expression(expression, &Rc::new(RefCell::new(Environment::new())))
+    }
+
+    fn int(value: i64) -> Expression {
+        Expression::Literal(Literal::Integer(Integer { value, range: RANGE }))
+    }
+
+    fn prefix(operator: TokenEnum, expression: Expression) -> Expression {
+        Expression::Prefix(Prefix {
+            token: Token { token_type: operator, range: RANGE },
+            expression: Box::new(expression),
+            range: RANGE,
+        })
+    }
+
+    fn infix(operator: TokenEnum, left: Expression, right: Expression) -> Expression {
+        Expression::Infix(Infix {
+            token: Token { token_type: operator, range: RANGE },
+            left: Box::new(left),
+            right: Box::new(right),
+            range: RANGE,
+        })
+    }
+
+    #[test]
+    fn test_return_statements() {
+        assert_eq!(test_eval("return 10;"), Object::Integer(10));
+        assert_eq!(test_eval("return 10; return 9;"), Object::Integer(10));
+    }
+
+    #[test]
+    fn test_integer_expressions() {
+        let tests = vec![
+            (prefix(TokenEnum::MINUS, int(5)), Object::Integer(-5)),
+            (infix(TokenEnum::PLUS, int(5), int(10)), Object::Integer(15)),
+            (infix(TokenEnum::ASTERISK, infix(TokenEnum::MINUS, int(2), int(7)), int(3)), Object::Integer(-15)),
+            (infix(TokenEnum::SLASH, int(50), int(2)), Object::Integer(25)),
+        ];
+
+        for (expression, expected) in tests {
+            assert_eq!(test_eval_expression(&expression), expected);
+        }
+    }
+
+    #[test]
+    fn test_boolean_expressions() {
+        let tests = vec![
+            (infix(TokenEnum::LT, int(1), int(2)), Object::Boolean(true)),
+            (infix(TokenEnum::GT, int(1), int(2)), Object::Boolean(false)),
+            (infix(TokenEnum::EQ, int(1), int(1)), Object::Boolean(true)),
+            (infix(TokenEnum::NEQ, int(1), int(1)), Object::Boolean(false)),
+            (infix(TokenEnum::EQ, infix(TokenEnum::LT, int(1), int(2)), infix(TokenEnum::GT, int(3), int(2))), Object::Boolean(true)),
+            (prefix(TokenEnum::BANG, int(5)), Object::Boolean(false)),
+            (prefix(TokenEnum::BANG, prefix(TokenEnum::BANG, int(5))), Object::Boolean(true)),
+        ];
+
+        for (expression, expected) in tests {
+            assert_eq!(test_eval_expression(&expression), expected);
+        }
+    }
+
+    #[test]
+    fn test_error_handling() {
+        let boolean = || infix(TokenEnum::LT, int(1), int(2));
+        let tests = vec![
+            (infix(TokenEnum::PLUS, int(5), boolean()), "type mismatch: INTEGER + BOOLEAN"),
+            (prefix(TokenEnum::MINUS, boolean()), "unknown operator: -BOOLEAN"),
+            (infix(TokenEnum::PLUS, boolean(), boolean()), "unknown operator: BOOLEAN + BOOLEAN"),
+            (infix(TokenEnum::PLUS, prefix(TokenEnum::MINUS, boolean()), int(5)), "unknown operator: -BOOLEAN"),
+            (infix(TokenEnum::SLASH, int(5), int(0)), "division by zero"),
+        ];
+
+        for (expression, expected) in tests {
+            assert_eq!(test_eval_expression(&expression), Object::Error(expected.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_identifiers() {
+        let env = Rc::new(RefCell::new(Environment::new()));
+        env.borrow_mut().set("five", Object::Integer(5));
+
+        let lexer = Lexer::new("return five;");
+        let program = Parser::new(lexer).parse_program().unwrap();
+        assert_eq!(eval_program(&program, &env), Object::Integer(5));
+
+        assert_eq!(test_eval("return foobar;"), Object::Error("identifier not found: foobar".to_string()));
+    }
+
+    #[test]
+    fn test_function_application() {
+        let tests = vec![
+            ("return fn(x) { return x; }(5);", Object::Integer(5)),
+            ("return fn(x, y) { return x + y; }(5, 5);", Object::Integer(10)),
+            ("return fn(x, y) { return x + y; }(5 + 5, fn() { return 10; }());", Object::Integer(20)),
+            ("return fn(x) { }(1);", Object::Null),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_closures() {
+        let input = "return fn(x) { return fn(y) { return x + y; }; }(2)(3);";
+
+        assert_eq!(test_eval(input), Object::Integer(5));
+    }
+
+    #[test]
+    fn test_function_errors() {
+        let tests = vec![
+            ("return fn(x) { return x; }();", "wrong number of arguments: want=1, got=0"),
+            ("return 5(1);", "not a function: INTEGER"),
+            ("return fn(f) { return f(f); }(fn(f) { return f(f); });", "stack overflow"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), Object::Error(expected.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_stack_limit() {
+        set_stack_limit(0);
+        assert_eq!(test_eval("return fn(x) { return x; }(1);"), Object::Integer(1));
+        assert_eq!(
+            test_eval("return fn(f) { return f(1); }(fn(x) { return x; });"),
+            Object::Error("stack overflow".to_string())
+        );
+
+        set_stack_limit(DEFAULT_STACK_LIMIT);
+        assert_eq!(test_eval("return fn(f) { return f(1); }(fn(x) { return x; });"), Object::Integer(1));
+    }
+
+    #[test]
+    fn test_deep_recursion() {
+        let input = "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; f(300)";
+        assert_eq!(test_eval(input), Object::Integer(300));
+
+        // a thread with a larger stack can allow deeper recursion
+        let thread = std::thread::Builder::new().stack_size(64 << 20).spawn(|| {
+            set_stack_limit(60 << 20);
+            let input = "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; f(5000)";
+            assert_eq!(test_eval(input), Object::Integer(5000));
+        });
+        thread.unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn test_if_else_expressions() {
+        let tests = vec![
+            ("return if (1 < 2) { return 10; };", Object::Integer(10)),
+            ("return if (1 > 2) { return 10; };", Object::Null),
+            ("return if (1 > 2) { return 10; } else { return 20; };", Object::Integer(20)),
+            ("return if (1 > 2) { return 10; } else if (1 == 1) { return 30; } else { return 20; };", Object::Integer(30)),
+            ("return if (1) { return 10; };", Object::Integer(10)),
+            ("return if (1 < 2) { return if (1 < 2) { return 10; }; return 1; };", Object::Integer(10)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_prefix_and_grouped_expressions() {
+        let tests = vec![
+            ("return !true;", Object::Boolean(false)),
+            ("return !!false;", Object::Boolean(false)),
+            ("return -5;", Object::Integer(-5)),
+            ("return -(5 + 10);", Object::Integer(-15)),
+            ("return (1 + 2) * 3;", Object::Integer(9)),
+            ("return (5 > 1) == true;", Object::Boolean(true)),
+            ("return -true;", Object::Error("unknown operator: -BOOLEAN".to_string())),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_expression_statements() {
+        let tests = vec![
+            ("5", Object::Integer(5)),
+            ("5 + 5 + 5 + 5 - 10", Object::Integer(10)),
+            ("-50 + 100 + -50", Object::Integer(0)),
+            ("3 * (3 * 3) + 10", Object::Integer(37)),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", Object::Integer(50)),
+            ("1 < 2 == true", Object::Boolean(true)),
+            ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
+            ("fn(x) { x * 2; }(4)", Object::Integer(8)),
+            ("9; return 2 * 5; 9;", Object::Integer(10)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_function_inspect() {
+        assert_eq!(test_eval("fn(x) { x + 2; }").to_string(), "fn(x) { (x + 2) }");
+    }
+
+    #[test]
+    fn test_let_statements() {
+        let tests = vec![
+            ("let a = 5; a;", Object::Integer(5)),
+            ("let a = 5 * 5; a;", Object::Integer(25)),
+            ("let a = 5; let b = a; b;", Object::Integer(5)),
+            ("let a = 5; let b = a; let c = a + b + 5; c;", Object::Integer(15)),
+            ("let a = 5", Object::Null),
+            ("let a = b;", Object::Error("identifier not found: b".to_string())),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_closures_over_let_bindings() {
+        let tests = vec![
+            ("let newAdder = fn(x) { fn(y) { x + y }; }; let addTwo = newAdder(2); addTwo(3);", Object::Integer(5)),
+            ("let identity = fn(x) { x; }; identity(5);", Object::Integer(5)),
+            ("let x = 1; let f = fn() { x }; let x = 2; f();", Object::Integer(2)),
+            ("let fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } }; fact(5);", Object::Integer(120)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_strings() {
+        let tests = vec![
+            (r#""Hello World!""#, Object::String("Hello World!".to_string())),
+            (r#""Hello" + " " + "World!""#, Object::String("Hello World!".to_string())),
+            (r#"let greet = fn(name) { "hi, " + name }; greet("bob")"#, Object::String("hi, bob".to_string())),
+            (r#""a" == "a""#, Object::Boolean(true)),
+            (r#""a" != "a""#, Object::Boolean(false)),
+            (r#""a" == "b""#, Object::Boolean(false)),
+            (r#""a" - "b""#, Object::Error("unknown operator: STRING - STRING".to_string())),
+            (r#""a" + 1"#, Object::Error("type mismatch: STRING + INTEGER".to_string())),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_array_literals() {
+        let expected = Object::Array(vec![Object::Integer(1), Object::Integer(4), Object::Integer(6)]);
+
+        assert_eq!(test_eval("[1, 2 * 2, 3 + 3]"), expected);
+        assert_eq!(test_eval("[1, a, 3]"), Object::Error("identifier not found: a".to_string()));
+    }
+
+    #[test]
+    fn test_array_index_expressions() {
+        let tests = vec![
+            ("[1, 2, 3][0]", Object::Integer(1)),
+            ("[1, 2, 3][2]", Object::Integer(3)),
+            ("let i = 0; [1][i];", Object::Integer(1)),
+            ("[1, 2, 3][1 + 1];", Object::Integer(3)),
+            ("let myArray = [1, 2, 3]; myArray[0] + myArray[1] + myArray[2];", Object::Integer(6)),
+            ("let myArray = [1, 2, 3]; let i = myArray[0]; myArray[i]", Object::Integer(2)),
+            ("[fn(x) { x * 2 }][0](21)", Object::Integer(42)),
+            ("[1, 2, 3][3]", Object::Null),
+            ("[1, 2, 3][-1]", Object::Null),
+            ("1[0]", Object::Error("index operator not supported: INTEGER".to_string())),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_hash_literals() {
+        let input = r#"
+            let two = "two";
+            {
+                "one": 10 - 9,
+                two: 1 + 1,
+                "thr" + "ee": 6 / 2,
+                4: 4,
+                true: 5,
+                false: 6
+            }
+        "#;
+
+        let Object::Hash(pairs) = test_eval(input) else {
+            panic!("expected a hash");
+        };
+
+        let expected = vec![
+            (Object::String("one".to_string()), Object::Integer(1)),
+            (Object::String("two".to_string()), Object::Integer(2)),
+            (Object::String("three".to_string()), Object::Integer(3)),
+            (Object::Integer(4), Object::Integer(4)),
+            (Object::Boolean(true), Object::Integer(5)),
+            (Object::Boolean(false), Object::Integer(6)),
+        ];
+
+        assert_eq!(pairs.len(), expected.len());
+        for (key, value) in expected {
+            assert_eq!(pairs.get(&key.hash_key().unwrap()), Some(&value));
+        }
+    }
+
+    #[test]
+    fn test_hash_index_expressions() {
+        let tests = vec![
+            (r#"{"foo": 5}["foo"]"#, Object::Integer(5)),
+            (r#"{"foo": 5}["bar"]"#, Object::Null),
+            (r#"let key = "foo"; {"foo": 5}[key]"#, Object::Integer(5)),
+            (r#"{}["foo"]"#, Object::Null),
+            ("{5: 5}[5]", Object::Integer(5)),
+            ("{true: 5}[true]", Object::Integer(5)),
+            ("{false: 5}[false]", Object::Integer(5)),
+            (r#"{"name": "Monkey"}[fn(x) { x }];"#, Object::Error("unusable as hash key: FUNCTION".to_string())),
+            (r#"{fn(x) { x }: "Monkey"};"#, Object::Error("unusable as hash key: FUNCTION".to_string())),
+            ("{[1]: 2};", Object::Error("unusable as hash key: ARRAY".to_string())),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_builtin_functions() {
+        let tests = vec![
+            (r#"len("four")"#, Object::Integer(4)),
+            ("len([1, 2, 3])", Object::Integer(3)),
+            ("len(1)", Object::Error("argument to `len` not supported, got INTEGER".to_string())),
+            ("first(rest(push([1, 2], 3)))", Object::Integer(2)),
+            ("last([1, 2, 3])", Object::Integer(3)),
+            ("let len = fn(x) { 42 }; len([1])", Object::Integer(42)),
+            (r#"puts("hello")"#, Object::Null),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_map_and_reduce_with_builtins() {
+        let input = "
+            let map = fn(arr, f) {
+                let iter = fn(arr, accumulated) {
+                    if (len(arr) == 0) {
+                        accumulated
+                    } else {
+                        iter(rest(arr), push(accumulated, f(first(arr))));
+                    }
+                };
+                iter(arr, []);
+            };
+            let reduce = fn(arr, initial, f) {
+                let iter = fn(arr, result) {
+                    if (len(arr) == 0) { result } else { iter(rest(arr), f(result, first(arr))) }
+                };
+                iter(arr, initial);
+            };
+            reduce(map([1, 2, 3, 4], fn(x) { x * 2 }), 0, fn(acc, x) { acc + x });
+        ";
+
+        assert_eq!(test_eval(input), Object::Integer(20));
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::hash::Hash;
+
+use crate::object::object::Object;
+
+pub type ConversionError = String;
+
+pub trait IntoObject {
+    fn into_object(self) -> Object;
+}
+
+pub trait FromObject: Sized {
+    fn from_object(object: Object) -> Result<Self, ConversionError>;
+}
+
+fn mismatch(expected: &str, object: &Object) -> ConversionError {
+    format!("expected {}, got {}", expected, object.type_name())
+}
+
+impl IntoObject for Object {
+    fn into_object(self) -> Object {
+        self
+    }
+}
+
+impl FromObject for Object {
+    fn from_object(object: Object) -> Result<Self, ConversionError> {
+        Ok(object)
+    }
+}
+
+impl IntoObject for () {
+    fn into_object(self) -> Object {
+        Object::Null
+    }
+}
+
+impl IntoObject for i64 {
+    fn into_object(self) -> Object {
+        Object::Integer(self)
+    }
+}
+
+impl FromObject for i64 {
+    fn from_object(object: Object) -> Result<Self, ConversionError> {
+        match object {
+            Object::Integer(value) => Ok(value),
+            other => Err(mismatch("INTEGER", &other)),
+        }
+    }
+}
+
+impl IntoObject for bool {
+    fn into_object(self) -> Object {
+        Object::Boolean(self)
+    }
+}
+
+impl FromObject for bool {
+    fn from_object(object: Object) -> Result<Self, ConversionError> {
+        match object {
+            Object::Boolean(value) => Ok(value),
+            other => Err(mismatch("BOOLEAN", &other)),
+        }
+    }
+}
+
+impl IntoObject for String {
+    fn into_object(self) -> Object {
+        Object::String(self)
+    }
+}
+
+impl IntoObject for &str {
+    fn into_object(self) -> Object {
+        Object::String(self.to_string())
+    }
+}
+
+impl FromObject for String {
+    fn from_object(object: Object) -> Result<Self, ConversionError> {
+        match object {
+            Object::String(value) => Ok(value),
+            other => Err(mismatch("STRING", &other)),
+        }
+    }
+}
+
+impl<T: IntoObject> IntoObject for Option<T> {
+    fn into_object(self) -> Object {
+        match self {
+            Some(value) => value.into_object(),
+            None => Object::Null,
+        }
+    }
+}
+
+impl<T: FromObject> FromObject for Option<T> {
+    fn from_object(object: Object) -> Result<Self, ConversionError> {
+        match object {
+            Object::Null => Ok(None),
+            other => T::from_object(other).map(Some),
+        }
+    }
+}
+
+// an element that fails to convert turns the whole array into its error object
+impl<T: IntoObject> IntoObject for Vec<T> {
+    fn into_object(self) -> Object {
+        let mut elements = Vec::with_capacity(self.len());
+
+        for element in self {
+            match element.into_object() {
+                error @ Object::Error(_) => return error,
+                element => elements.push(element),
+            }
+        }
+
+        Object::Array(elements)
+    }
+}
+
+impl<T: FromObject> FromObject for Vec<T> {
+    fn from_object(object: Object) -> Result<Self, ConversionError> {
+        match object {
+            Object::Array(elements) => elements.into_iter().map(T::from_object).collect(),
+            other => Err(mismatch("ARRAY", &other)),
+        }
+    }
+}
+
+// keys that can't be hashed by Monkey (anything but integers, booleans and strings) and values that
+// fail to convert turn the whole map into an error object
+impl<K: IntoObject, V: IntoObject> IntoObject for HashMap<K, V> {
+    fn into_object(self) -> Object {
+        let mut pairs = BTreeMap::new();
+
+        for (key, value) in self {
+            let key = key.into_object();
+            let Some(hash_key) = key.hash_key() else {
+                return Object::Error(format!("unusable as hash key: {}", key.type_name()));
+            };
+            match value.into_object() {
+                error @ Object::Error(_) => return error,
+                value => pairs.insert(hash_key, value),
+            };
+        }
+
+        Object::Hash(pairs)
+    }
+}
+
+impl<K: FromObject + Eq + Hash, V: FromObject> FromObject for HashMap<K, V> {
+    fn from_object(object: Object) -> Result<Self, ConversionError> {
+        match object {
+            Object::Hash(pairs) => pairs
+                .into_iter()
+                .map(|(key, value)| Ok((K::from_object(key.into())?, V::from_object(value)?)))
+                .collect(),
+            other => Err(mismatch("HASH", &other)),
+        }
+    }
+}
+
+// lets host callbacks fail by returning Err, which Monkey code sees as an error object
+impl<T: IntoObject, E: Display> IntoObject for Result<T, E> {
+    fn into_object(self) -> Object {
+        match self {
+            Ok(value) => value.into_object(),
+            Err(error) => Object::Error(error.to_string()),
+        }
+    }
+}
+
+pub type HostCallback = Box<dyn Fn(Vec<Object>) -> Object>;
+
+// implemented for closures taking up to four FromObject arguments and returning an IntoObject,
+// `Args` only exists to keep the impls for different arities apart
+pub trait IntoHostFunction<Args> {
+    fn into_host_function(self) -> HostCallback;
+}
+
+macro_rules! impl_into_host_function {
+    ($arity:expr $(, $arg:ident)*) => {
+        impl<F, R, $($arg,)*> IntoHostFunction<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: IntoObject,
+            $($arg: FromObject,)*
+        {
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn into_host_function(self) -> HostCallback {
+                Box::new(move |arguments: Vec<Object>| {
+                    if arguments.len() != $arity {
+                        return Object::Error(format!(
+                            "wrong number of arguments. got={}, want={}",
+                            arguments.len(),
+                            $arity
+                        ));
+                    }
+
+                    let mut arguments = arguments.into_iter();
+                    $(
+                        let $arg = match $arg::from_object(arguments.next().unwrap()) {
+                            Ok(value) => value,
+                            Err(error) => return Object::Error(error),
+                        };
+                    )*
+
+                    self($($arg),*).into_object()
+                })
+            }
+        }
+    };
+}
+
+impl_into_host_function!(0);
+impl_into_host_function!(1, A);
+impl_into_host_function!(2, A, B);
+impl_into_host_function!(3, A, B, C);
+impl_into_host_function!(4, A, B, C, D);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips() {
+        assert_eq!(i64::from_object(5.into_object()), Ok(5));
+        assert_eq!(bool::from_object(true.into_object()), Ok(true));
+        assert_eq!(String::from_object("monkey".into_object()), Ok("monkey".to_string()));
+        assert_eq!(Vec::<i64>::from_object(vec![1, 2, 3].into_object()), Ok(vec![1, 2, 3]));
+        assert_eq!(Option::<i64>::from_object(Object::Null), Ok(None));
+
+        let map = HashMap::from([("a".to_string(), 1), ("b".to_string(), 2)]);
+        assert_eq!(HashMap::<String, i64>::from_object(map.clone().into_object()), Ok(map));
+    }
+
+    #[test]
+    fn test_conversion_errors() {
+        assert_eq!(i64::from_object(Object::Boolean(true)), Err("expected INTEGER, got BOOLEAN".to_string()));
+        assert_eq!(
+            Vec::<i64>::from_object(vec![Object::Integer(1), Object::Null].into_object()),
+            Err("expected INTEGER, got NULL".to_string())
+        );
+        assert_eq!(
+            HashMap::from([(vec![1], 1)]).into_object(),
+            Object::Error("unusable as hash key: ARRAY".to_string())
+        );
+        assert_eq!(
+            vec![HashMap::from([(vec![1], 1)])].into_object(),
+            Object::Error("unusable as hash key: ARRAY".to_string())
+        );
+    }
+
+    #[test]
+    fn test_host_function_arguments() {
+        let add = (|a: i64, b: i64| a + b).into_host_function();
+
+        assert_eq!(add(vec![Object::Integer(1), Object::Integer(2)]), Object::Integer(3));
+        assert_eq!(
+            add(vec![Object::Integer(1)]),
+            Object::Error("wrong number of arguments. got=1, want=2".to_string())
+        );
+        assert_eq!(
+            add(vec![Object::Integer(1), Object::Boolean(true)]),
+            Object::Error("expected INTEGER, got BOOLEAN".to_string())
+        );
+    }
+}
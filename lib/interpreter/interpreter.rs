@@ -0,0 +1,197 @@
+use core::fmt;
+use std::{cell::RefCell, rc::Rc};
+
+use crate::evaluator::evaluator::eval_program;
+use crate::lexer::lexer::Lexer;
+use crate::object::environment::{Env, Environment};
+use crate::object::object::{HostFunction, Object};
+use crate::parser::parser::{ParseError, Parser};
+
+use super::convert::{FromObject, IntoHostFunction, IntoObject};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InterpreterError {
+    Parse(Vec<ParseError>),
+    Runtime(String),
+    Conversion(String),
+    Undefined(String),
+}
+
+impl fmt::Display for InterpreterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InterpreterError::Parse(errors) => {
+                let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+                write!(f, "parse errors: {}", messages.join("; "))
+            },
+            InterpreterError::Runtime(message) => write!(f, "runtime error: {}", message),
+            InterpreterError::Conversion(message) => write!(f, "conversion error: {}", message),
+            InterpreterError::Undefined(name) => write!(f, "undefined global: {}", name),
+        }
+    }
+}
+
+impl std::error::Error for InterpreterError {}
+
+// a Monkey session for embedding: globals and registered host functions persist between calls
+//
+// a function holds on to the environment it was defined in, so a global function and the globals
+// form a reference cycle; the globals are cleared when the interpreter is dropped to break it.
+// cycles that never reach the globals, like a function defined inside another function and
+// returned to the host, are not collected and live until the process exits
+pub struct Interpreter {
+    env: Env,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Interpreter {
+    fn drop(&mut self) {
+        self.env.borrow_mut().clear();
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter {
+            env: Rc::new(RefCell::new(Environment::new())),
+        }
+    }
+
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    pub fn eval_str<T: FromObject>(&mut self, input: &str) -> Result<T, InterpreterError> {
+        let program = Parser::new(Lexer::new(input)).parse_program().map_err(InterpreterError::Parse)?;
+
+        match eval_program(&program, &self.env) {
+            Object::Error(message) => Err(InterpreterError::Runtime(message)),
+            result => T::from_object(result).map_err(InterpreterError::Conversion),
+        }
+    }
+
+    // a value that can't be converted, like a map with keys Monkey can't hash, is rejected rather
+    // than stored as an error object
+    pub fn set_global<T: IntoObject>(&mut self, name: &str, value: T) -> Result<(), InterpreterError> {
+        match value.into_object() {
+            Object::Error(message) => Err(InterpreterError::Conversion(message)),
+            value => {
+                self.env.borrow_mut().set(name, value);
+                Ok(())
+            },
+        }
+    }
+
+    pub fn get_global<T: FromObject>(&self, name: &str) -> Result<T, InterpreterError> {
+        match self.env.borrow().get(name) {
+            Some(value) => T::from_object(value).map_err(InterpreterError::Conversion),
+            None => Err(InterpreterError::Undefined(name.to_string())),
+        }
+    }
+
+    pub fn register_fn<Args, F: IntoHostFunction<Args>>(&mut self, name: &str, function: F) {
+        let host = HostFunction {
+            name: name.to_string(),
+            function: function.into_host_function(),
+        };
+        self.env.borrow_mut().set(name, Object::HostFunction(Rc::new(host)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_eval_str() {
+        let mut interpreter = Interpreter::new();
+
+        assert_eq!(interpreter.eval_str::<i64>("60 * 60 * 24"), Ok(86400));
+        assert_eq!(interpreter.eval_str::<Object>("let x = 5;"), Ok(Object::Null));
+        assert_eq!(interpreter.eval_str::<bool>("x == 5"), Ok(true));
+        assert_eq!(interpreter.eval_str::<Vec<String>>(r#"["a", "b"]"#), Ok(vec!["a".to_string(), "b".to_string()]));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut interpreter = Interpreter::new();
+
+        assert!(matches!(interpreter.eval_str::<Object>("let = 5;"), Err(InterpreterError::Parse(_))));
+        assert_eq!(
+            interpreter.eval_str::<Object>("1 + true"),
+            Err(InterpreterError::Runtime("type mismatch: INTEGER + BOOLEAN".to_string()))
+        );
+        assert_eq!(
+            interpreter.eval_str::<i64>("true"),
+            Err(InterpreterError::Conversion("expected INTEGER, got BOOLEAN".to_string()))
+        );
+        assert_eq!(interpreter.get_global::<i64>("nope"), Err(InterpreterError::Undefined("nope".to_string())));
+    }
+
+    #[test]
+    fn test_globals() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_global("limits", HashMap::from([("cpu".to_string(), 2), ("memory".to_string(), 512)])).unwrap();
+        interpreter.set_global("name", "worker").unwrap();
+
+        interpreter
+            .eval_str::<Object>(r#"let total = limits["cpu"] * limits["memory"]; let label = name + "-1";"#)
+            .unwrap();
+
+        assert_eq!(interpreter.get_global::<i64>("total"), Ok(1024));
+        assert_eq!(interpreter.get_global::<String>("label"), Ok("worker-1".to_string()));
+
+        assert_eq!(
+            interpreter.set_global("bad", HashMap::from([(vec![1], 1)])),
+            Err(InterpreterError::Conversion("unusable as hash key: ARRAY".to_string()))
+        );
+        assert_eq!(interpreter.get_global::<Object>("bad"), Err(InterpreterError::Undefined("bad".to_string())));
+    }
+
+    #[test]
+    fn test_register_fn() {
+        let mut interpreter = Interpreter::new();
+        interpreter.register_fn("add", |a: i64, b: i64| a + b);
+        interpreter.register_fn("shout", |s: String| s.to_uppercase());
+        interpreter.register_fn("checked_div", |a: i64, b: i64| {
+            a.checked_div(b).ok_or("cannot divide by zero")
+        });
+
+        let counter = Rc::new(RefCell::new(0));
+        let calls = Rc::clone(&counter);
+        interpreter.register_fn("tick", move || {
+            *calls.borrow_mut() += 1;
+        });
+
+        assert_eq!(interpreter.eval_str::<i64>("add(1, add(2, 3))"), Ok(6));
+        assert_eq!(interpreter.eval_str::<String>(r#"shout("hi")"#), Ok("HI".to_string()));
+        assert_eq!(
+            interpreter.eval_str::<i64>("checked_div(1, 0)"),
+            Err(InterpreterError::Runtime("cannot divide by zero".to_string()))
+        );
+        assert_eq!(
+            interpreter.eval_str::<i64>(r#"add(1, "2")"#),
+            Err(InterpreterError::Runtime("expected INTEGER, got STRING".to_string()))
+        );
+
+        interpreter.eval_str::<Object>("tick(); tick();").unwrap();
+        assert_eq!(*counter.borrow(), 2);
+    }
+
+    #[test]
+    fn test_drop_frees_globals() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str::<Object>("let f = fn(x) { f(x) }; let g = fn() { f };").unwrap();
+        let env = Rc::downgrade(interpreter.env());
+
+        drop(interpreter);
+        assert!(env.upgrade().is_none());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::token::token::{Token, TokenEnum, lookup_ident, TokenRange};
+use crate::token::token::{Illegal, Token, TokenEnum, lookup_ident, TokenRange};
 
 #[derive(Clone, Debug)]
 pub struct Lexer<'a> {
@@ -22,17 +22,15 @@ impl<'a> Lexer<'a> {
     }
 
     fn read_char(&mut self) {
-        if self.read_position >= self.input.len() {
-            self.ch = 0 as char;
-        } else {
-            if let Some(ch) = self.input.chars().nth(self.read_position) {
+        self.position = self.read_position;
+
+        match self.input.get(self.read_position..).and_then(|rest| rest.chars().next()) {
+            Some(ch) => {
                 self.ch = ch;
-            } else {
-                panic!("read_char failed: read out of range")
-            }
+                self.read_position += ch.len_utf8();
+            },
+            None => self.ch = 0 as char,
         }
-        self.position = self.read_position;
-        self.read_position += 1;
     }
 
     fn skip_whitespace(&mut self) {
@@ -44,6 +42,7 @@ impl<'a> Lexer<'a> {
     pub fn next_token(&mut self) -> Token {
         self.skip_whitespace();
 
+        let start = self.position;
         let token_type = match self.ch {
             '=' => {
                 if self.peek_char() == '=' {
@@ -55,12 +54,15 @@ impl<'a> Lexer<'a> {
                 }
             },
             ';' => TokenEnum::SEMICOLON,
+            ':' => TokenEnum::COLON,
             '(' => TokenEnum::LPAREN,
             ')' => TokenEnum::RPAREN,
             ',' => TokenEnum::COMMA,
             '+' => TokenEnum::PLUS,
             '{' => TokenEnum::LBRACE,
             '}' => TokenEnum::RBRACE,
+            '[' => TokenEnum::LBRACKET,
+            ']' => TokenEnum::RBRACKET,
             '-' => TokenEnum::MINUS,
             '!' => {
                 if self.peek_char() == '=' {
@@ -75,25 +77,28 @@ impl<'a> Lexer<'a> {
             '/' => TokenEnum::SLASH,
             '<' => TokenEnum::LT,
             '>' => TokenEnum::GT,
-            '\0' => TokenEnum::EOF,
+            '"' => return self.read_string(),
+            '\0' => {
+                return Token {
+                    token_type: TokenEnum::EOF,
+                    range: TokenRange { start, end: start }
+                };
+            },
             _ => {
-                if self.ch.is_alphabetic() {
+                if self.ch.is_alphabetic() || self.ch == '_' {
                     let (literal, start, end)  = self.read_identifier();
                     return Token {
                         token_type: lookup_ident(&literal),
                         range: TokenRange { start, end  }
                     };
-                } else if self.ch.is_digit(10) {
+                } else if self.ch.is_ascii_digit() {
                     let (int, start, end) = self.read_int();
                     return Token {
-                        token_type: TokenEnum::INT(int),
+                        token_type: int.map_or(TokenEnum::ILLEGAL(Illegal::IntegerOutOfRange), TokenEnum::INT),
                         range: TokenRange { start, end }
                     };
                 } else {
-                    return Token {
-                        token_type: TokenEnum::ILLEGAL,
-                        range: TokenRange { start: self.position, end: self.position }
-                    };
+                    TokenEnum::ILLEGAL(Illegal::Character(self.ch))
                 }
             },
         };
@@ -101,26 +106,94 @@ impl<'a> Lexer<'a> {
         self.read_char();
         Token {
             token_type,
-            range: TokenRange { start: self.position - 1, end: self.read_position - 1 }
+            range: TokenRange { start, end: self.position }
         }
     }
 
-    fn read_int(&mut self) -> (u32, usize, usize) {
+    // an unterminated literal or one with a bad escape is lexed as a single ILLEGAL token covering
+    // everything up to the closing quote (or the end of input)
+    fn read_string(&mut self) -> Token {
+        let start = self.position;
+        let mut value = String::new();
+        let mut valid = true;
+
+        loop {
+            self.read_char();
+
+            match self.ch {
+                '"' => break,
+                '\0' if self.position >= self.input.len() => {
+                    return Token {
+                        token_type: TokenEnum::ILLEGAL(Illegal::UnterminatedString),
+                        range: TokenRange { start, end: self.position }
+                    };
+                },
+                '\\' => {
+                    self.read_char();
+
+                    let escaped = match self.ch {
+                        'n' => Some('\n'),
+                        't' => Some('\t'),
+                        '"' => Some('"'),
+                        '\\' => Some('\\'),
+                        'u' => self.read_unicode_escape(),
+                        _ => None,
+                    };
+
+                    match escaped {
+                        Some(ch) => value.push(ch),
+                        None => valid = false,
+                    }
+                },
+                ch => value.push(ch),
+            }
+        }
+
+        self.read_char();
+        Token {
+            token_type: if valid { TokenEnum::STRING(value) } else { TokenEnum::ILLEGAL(Illegal::InvalidEscape) },
+            range: TokenRange { start, end: self.position }
+        }
+    }
+
+    // reads the `{1F600}` part of a `\u{1F600}` escape, leaving the lexer on the closing brace
+    fn read_unicode_escape(&mut self) -> Option<char> {
+        if self.peek_char() != '{' {
+            return None;
+        }
+        self.read_char();
+
+        let mut digits = String::new();
+        while self.peek_char().is_ascii_hexdigit() {
+            self.read_char();
+            digits.push(self.ch);
+        }
+
+        if self.peek_char() != '}' {
+            return None;
+        }
+        self.read_char();
+
+        u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32)
+    }
+
+    // a literal too large for an integer comes back as None, to be lexed as ILLEGAL
+    fn read_int(&mut self) -> (Option<i64>, usize, usize) {
         let pos = self.position;
 
-        while self.ch.is_digit(10) {
+        while self.ch.is_ascii_digit() {
             self.read_char();
         }
 
         let input_slice = self.input.get(pos..self.position).unwrap();
 
-        (input_slice.to_string().parse::<u32>().unwrap(), pos, self.position)
+        (input_slice.parse::<i64>().ok(), pos, self.position)
     }
 
     fn read_identifier(&mut self) -> (String, usize, usize) {
         let pos = self.position;
 
-        while self.ch.is_alphabetic() {
+        while self.ch.is_alphanumeric() || self.ch == '_' {
             self.read_char();
         }
 
@@ -130,21 +203,18 @@ impl<'a> Lexer<'a> {
     }
 
     fn peek_char(&mut self) -> char {
-        let read_pos = usize::try_from(self.read_position).unwrap();
-
-        println!("read_pos {}, {}", read_pos, self.input.as_bytes()[read_pos] as char);
-
-        if read_pos >= self.input.len() {
-            '\0'
-        } else {
-            self.input.as_bytes()[read_pos as usize] as char
-        }
+        self.input
+            .get(self.read_position..)
+            .and_then(|rest| rest.chars().next())
+            .unwrap_or('\0')
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::unused_enumerate_index)]
 mod tests {
     use super::*;
+    use crate::token::token::KEYWORDS;
 
     // fn test_token_set(l: &mut Lexer) -> Vec<Token> {
     //     let mut token_vs: Vec<Token> = vec![];
@@ -302,4 +372,176 @@ mod tests {
             assert_eq!(token.token_type, *token_type);
         }
     }
+
+    #[test]
+    fn test_string_literals() {
+        let mut lex = Lexer::new(r#""foobar" "foo bar" "a\nb\t\"c\"\\" "\u{1F600}é" """#);
+
+        let tests = vec![
+            (TokenEnum::STRING("foobar".to_string()), (0, 8)),
+            (TokenEnum::STRING("foo bar".to_string()), (9, 18)),
+            (TokenEnum::STRING("a\nb\t\"c\"\\".to_string()), (19, 34)),
+            (TokenEnum::STRING("😀é".to_string()), (35, 48)),
+            (TokenEnum::STRING("".to_string()), (49, 51)),
+            (TokenEnum::EOF, (51, 51)),
+        ];
+
+        for (token_type, (start, end)) in tests {
+            let token = lex.next_token();
+
+            assert_eq!(token.token_type, token_type);
+            assert_eq!((token.range.start, token.range.end), (start, end));
+        }
+    }
+
+    #[test]
+    fn test_illegal_string_literals() {
+        let tests = vec![
+            (r#""bad \q escape" 5"#, (0, 15)),
+            (r#""\u{110000}" 5"#, (0, 12)),
+            (r#""\u{zz}" 5"#, (0, 8)),
+        ];
+
+        for (input, (start, end)) in tests {
+            let mut lex = Lexer::new(input);
+            let token = lex.next_token();
+
+            assert_eq!(token.token_type, TokenEnum::ILLEGAL(Illegal::InvalidEscape), "{}", input);
+            assert_eq!((token.range.start, token.range.end), (start, end), "{}", input);
+            assert_eq!(lex.next_token().token_type, TokenEnum::INT(5), "{}", input);
+        }
+
+        let mut lex = Lexer::new(r#"let s = "unterminated"#);
+        for _ in 0..3 {
+            lex.next_token();
+        }
+        let token = lex.next_token();
+        assert_eq!(token.token_type, TokenEnum::ILLEGAL(Illegal::UnterminatedString));
+        assert_eq!((token.range.start, token.range.end), (8, 21));
+        assert_eq!(lex.next_token().token_type, TokenEnum::EOF);
+    }
+
+    #[test]
+    fn test_integer_literals() {
+        let mut lex = Lexer::new("99999999999 9223372036854775807 9223372036854775808 5");
+
+        assert_eq!(lex.next_token().token_type, TokenEnum::INT(99999999999));
+        assert_eq!(lex.next_token().token_type, TokenEnum::INT(i64::MAX));
+
+        let token = lex.next_token();
+        assert_eq!(token.token_type, TokenEnum::ILLEGAL(Illegal::IntegerOutOfRange));
+        assert_eq!((token.range.start, token.range.end), (32, 51));
+        assert_eq!(lex.next_token().token_type, TokenEnum::INT(5));
+    }
+
+    #[test]
+    fn test_token_ranges() {
+        let mut lex = Lexer::new("a == b != c; @");
+
+        let tests = vec![(0, 1), (2, 4), (5, 6), (7, 9), (10, 11), (11, 12), (13, 14), (14, 14)];
+
+        for (start, end) in tests {
+            let token = lex.next_token();
+            assert_eq!((token.range.start, token.range.end), (start, end), "{:?}", token);
+        }
+    }
+
+    #[test]
+    fn test_brackets() {
+        let tests: Vec<TokenEnum> = [
+            TokenEnum::LBRACKET,
+            TokenEnum::INT(1),
+            TokenEnum::COMMA,
+            TokenEnum::INT(2),
+            TokenEnum::RBRACKET,
+            TokenEnum::LBRACKET,
+            TokenEnum::INT(0),
+            TokenEnum::RBRACKET,
+            TokenEnum::SEMICOLON,
+            TokenEnum::EOF,
+        ].to_vec();
+
+        let mut lex = Lexer::new("[1, 2][0];");
+
+        for token_type in tests.iter() {
+            let token = lex.next_token();
+
+            assert_eq!(token.token_type, *token_type);
+        }
+    }
+
+    #[test]
+    fn test_hash_tokens() {
+        let tests: Vec<TokenEnum> = [
+            TokenEnum::LBRACE,
+            TokenEnum::STRING("foo".to_string()),
+            TokenEnum::COLON,
+            TokenEnum::STRING("bar".to_string()),
+            TokenEnum::COMMA,
+            TokenEnum::INT(1),
+            TokenEnum::COLON,
+            TokenEnum::TRUE,
+            TokenEnum::RBRACE,
+            TokenEnum::EOF,
+        ].to_vec();
+
+        let mut lex = Lexer::new(r#"{"foo": "bar", 1: true}"#);
+
+        for token_type in tests.iter() {
+            let token = lex.next_token();
+
+            assert_eq!(token.token_type, *token_type);
+        }
+    }
+
+    #[test]
+    fn test_identifiers() {
+        let mut lex = Lexer::new("checked_div _tmp x1 1x");
+
+        let tests: Vec<TokenEnum> = [
+            TokenEnum::IDENT { name: "checked_div".to_string() },
+            TokenEnum::IDENT { name: "_tmp".to_string() },
+            TokenEnum::IDENT { name: "x1".to_string() },
+            TokenEnum::INT(1),
+            TokenEnum::IDENT { name: "x".to_string() },
+        ].to_vec();
+
+        for token_type in tests.iter() {
+            let token = lex.next_token();
+
+            assert_eq!(token.token_type, *token_type);
+        }
+    }
+
+    #[test]
+    fn test_line_col() {
+        let input = "let a = 1;\nlet é = \"x\";\n\n  a";
+        let mut lexer = Lexer::new(input);
+        let mut locations = Vec::new();
+
+        loop {
+            let token = lexer.next_token();
+            if token.token_type == TokenEnum::EOF {
+                locations.push(token.range.line_col(input));
+                break;
+            }
+            if token.token_type == TokenEnum::ASSIGN {
+                locations.push(token.range.line_col(input));
+            }
+        }
+
+        assert_eq!(locations, vec![(1, 7), (2, 7), (4, 4)]);
+
+        let at = |start| TokenRange { start, end: start }.line_col(input);
+        assert_eq!(at(16), (2, 5));
+        assert_eq!(at(100), (4, 4));
+    }
+
+    #[test]
+    fn test_keywords() {
+        for (keyword, token_type) in KEYWORDS {
+            assert_eq!(Lexer::new(keyword).next_token().token_type, *token_type);
+        }
+        assert_eq!(Lexer::new("lets").next_token().token_type, TokenEnum::IDENT { name: "lets".to_string() });
+    }
 }
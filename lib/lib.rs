@@ -1,3 +1,13 @@
+#![allow(clippy::module_inception)]
+
 pub mod lexer;
 pub mod token;
 pub mod parser;
+pub mod object;
+pub mod evaluator;
+pub mod builtins;
+pub mod interpreter;
+pub mod code;
+pub mod compiler;
+pub mod vm;
+pub mod mbc;
@@ -0,0 +1,550 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::builtins::builtins::BUILTINS;
+use crate::code::code::{read_operands, Opcode, Positions};
+use crate::compiler::compiler::Bytecode;
+use crate::object::object::{CompiledFunction, Object};
+use crate::token::token::TokenRange;
+
+// layout of a .mbc file, all integers big-endian:
+//
+//   magic "\0MBC", version: u16, flags: u8
+//   constant count: u32, then each constant as a tag byte followed by its payload
+//   the main program's code
+//
+// code is the instruction length (u32) and bytes, followed by the position table when the debug
+// flag is set: an entry count (u32) and (offset, start, end) u32 triples
+pub const MAGIC: &[u8; 4] = b"\0MBC";
+pub const VERSION: u16 = 1;
+
+const FLAG_DEBUG: u8 = 1;
+
+const TAG_INTEGER: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_FUNCTION: u8 = 2;
+
+pub type MbcError = String;
+
+// serializes compiled bytecode; positions are only written when debug is set
+pub fn encode(bytecode: &Bytecode, debug: bool) -> Result<Vec<u8>, MbcError> {
+    let mut writer = Writer { bytes: Vec::new(), debug };
+
+    writer.bytes.extend_from_slice(MAGIC);
+    writer.bytes.extend_from_slice(&VERSION.to_be_bytes());
+    writer.bytes.push(if debug { FLAG_DEBUG } else { 0 });
+
+    writer.write_len(bytecode.constants.len())?;
+    for constant in &bytecode.constants {
+        writer.write_constant(constant)?;
+    }
+    writer.write_code(&bytecode.instructions, &bytecode.positions)?;
+
+    Ok(writer.bytes)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Bytecode, MbcError> {
+    let mut reader = Reader { bytes, offset: 0, debug: false };
+
+    if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+        return Err("not a Monkey bytecode file".to_string());
+    }
+    let version = reader.read_u16()?;
+    if version != VERSION {
+        return Err(format!("unsupported bytecode version {}, expected {}", version, VERSION));
+    }
+    reader.debug = reader.read_u8()? & FLAG_DEBUG != 0;
+
+    let count = reader.read_u32()?;
+    let mut constants = Vec::new();
+    for _ in 0..count {
+        constants.push(reader.read_constant()?);
+    }
+    let (instructions, positions) = reader.read_code()?;
+
+    if reader.offset != bytes.len() {
+        return Err(format!("unexpected data after the program at byte {}", reader.offset));
+    }
+
+    validate(&instructions, &constants)?;
+
+    Ok(Bytecode { instructions, constants, positions })
+}
+
+struct Instruction {
+    offset: usize,
+    op: Opcode,
+    operands: Vec<usize>,
+}
+
+// what the VM provides to a piece of code when it runs it
+struct Context {
+    num_locals: usize,
+    // None for a function no closure is ever created from, which can't run
+    num_free: Option<usize>,
+    // functions have to return, while the main program may also run off its end
+    is_function: bool,
+}
+
+// the VM trusts its bytecode, so anything that would make it read out of bounds or pop from an
+// empty stack is rejected here
+fn validate(instructions: &[u8], constants: &[Object]) -> Result<(), MbcError> {
+    let main = split_instructions(instructions)?;
+    let mut functions = Vec::new();
+    for (index, constant) in constants.iter().enumerate() {
+        if let Object::CompiledFunction(function) = constant {
+            functions.push((index, function, split_instructions(&function.instructions)?));
+        }
+    }
+
+    // a function's free variables come from the closures created from it; if they are created
+    // with different counts, only the smallest is safe to read from
+    let mut num_free: HashMap<usize, usize> = HashMap::new();
+    for code in std::iter::once(&main).chain(functions.iter().map(|(_, _, code)| code)) {
+        for instruction in code.iter().filter(|instruction| instruction.op == Opcode::Closure) {
+            let count = num_free.entry(instruction.operands[0]).or_insert(instruction.operands[1]);
+            *count = (*count).min(instruction.operands[1]);
+        }
+    }
+
+    let context = Context { num_locals: 0, num_free: Some(0), is_function: false };
+    validate_code(&main, instructions.len(), &context, constants)?;
+
+    for (index, function, code) in &functions {
+        let context = Context {
+            num_locals: function.num_locals,
+            num_free: num_free.get(index).copied(),
+            is_function: true,
+        };
+        validate_code(code, function.instructions.len(), &context, constants)?;
+    }
+
+    Ok(())
+}
+
+fn split_instructions(instructions: &[u8]) -> Result<Vec<Instruction>, MbcError> {
+    let mut code = Vec::new();
+    let mut offset = 0;
+
+    while offset < instructions.len() {
+        let op = Opcode::from_byte(instructions[offset])
+            .ok_or_else(|| format!("unknown opcode {} at {}", instructions[offset], offset))?;
+        let definition = op.definition();
+
+        let (operands, read) = read_operands(&definition, &instructions[offset + 1..])
+            .ok_or_else(|| format!("truncated {} at {}", definition.name, offset))?;
+
+        code.push(Instruction { offset, op, operands });
+        offset += 1 + read;
+    }
+
+    Ok(code)
+}
+
+fn validate_code(code: &[Instruction], len: usize, context: &Context, constants: &[Object]) -> Result<(), MbcError> {
+    for instruction in code {
+        let operand = instruction.operands.first().copied().unwrap_or(0);
+
+        let valid = match instruction.op {
+            Opcode::Constant => operand < constants.len(),
+            Opcode::Closure => matches!(constants.get(operand), Some(Object::CompiledFunction(_))),
+            // jumps have to land at the start of an instruction
+            Opcode::Jump | Opcode::JumpNotTruthy => {
+                operand == len || code.binary_search_by_key(&operand, |instruction| instruction.offset).is_ok()
+            },
+            Opcode::GetLocal | Opcode::SetLocal => operand < context.num_locals,
+            Opcode::GetBuiltin => operand < BUILTINS.len(),
+            Opcode::GetFree => context.num_free.is_none_or(|num_free| operand < num_free),
+            // keys and values come in pairs
+            Opcode::Hash => operand % 2 == 0,
+            _ => true,
+        };
+        if !valid {
+            return Err(format!(
+                "invalid operand {} for {} at {}",
+                operand,
+                instruction.op.definition().name,
+                instruction.offset
+            ));
+        }
+    }
+
+    validate_stack(code, context)
+}
+
+// follows every path through the code, tracking how many values each instruction leaves on the
+// stack; paths that meet have to agree on it, so the depth at each instruction is known
+fn validate_stack(code: &[Instruction], context: &Context) -> Result<(), MbcError> {
+    let mut depths: Vec<Option<usize>> = vec![None; code.len()];
+    let mut pending = vec![(0, 0)];
+
+    while let Some((index, depth)) = pending.pop() {
+        let Some(instruction) = code.get(index) else {
+            if context.is_function {
+                return Err("missing return at the end of a function".to_string());
+            }
+            continue;
+        };
+
+        match depths[index] {
+            Some(known) if known == depth => continue,
+            Some(_) => return Err(format!("inconsistent stack depth at {}", instruction.offset)),
+            None => depths[index] = Some(depth),
+        }
+
+        let (pops, pushes) = stack_effect(instruction);
+        if depth < pops {
+            return Err(format!(
+                "stack underflow in {} at {}",
+                instruction.op.definition().name,
+                instruction.offset
+            ));
+        }
+        let depth = depth - pops + pushes;
+
+        let target = |offset: usize| code.partition_point(|instruction| instruction.offset < offset);
+        match instruction.op {
+            Opcode::Return | Opcode::ReturnValue => {},
+            Opcode::Jump => pending.push((target(instruction.operands[0]), depth)),
+            Opcode::JumpNotTruthy => {
+                pending.push((target(instruction.operands[0]), depth));
+                pending.push((index + 1, depth));
+            },
+            _ => pending.push((index + 1, depth)),
+        }
+    }
+
+    Ok(())
+}
+
+// how many values an instruction pops and then pushes; a call pops the callee with its arguments
+// and pushes the value it returns
+fn stack_effect(instruction: &Instruction) -> (usize, usize) {
+    match instruction.op {
+        Opcode::Constant
+        | Opcode::True
+        | Opcode::False
+        | Opcode::Null
+        | Opcode::GetGlobal
+        | Opcode::GetLocal
+        | Opcode::GetBuiltin
+        | Opcode::GetFree
+        | Opcode::CurrentClosure => (0, 1),
+        Opcode::Pop | Opcode::SetGlobal | Opcode::SetLocal | Opcode::JumpNotTruthy | Opcode::ReturnValue => (1, 0),
+        Opcode::Add
+        | Opcode::Sub
+        | Opcode::Mul
+        | Opcode::Div
+        | Opcode::Equal
+        | Opcode::NotEqual
+        | Opcode::GreaterThan
+        | Opcode::LessThan
+        | Opcode::Index => (2, 1),
+        Opcode::Minus | Opcode::Bang => (1, 1),
+        Opcode::Jump | Opcode::Return => (0, 0),
+        Opcode::Array | Opcode::Hash => (instruction.operands[0], 1),
+        Opcode::Closure => (instruction.operands[1], 1),
+        Opcode::Call => (instruction.operands[0] + 1, 1),
+    }
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+    debug: bool,
+}
+
+impl Writer {
+    fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn write_len(&mut self, len: usize) -> Result<(), MbcError> {
+        let len = u32::try_from(len).map_err(|_| format!("{} is too large for a bytecode file", len))?;
+        self.write_u32(len);
+        Ok(())
+    }
+
+    fn write_constant(&mut self, constant: &Object) -> Result<(), MbcError> {
+        match constant {
+            Object::Integer(value) => {
+                self.bytes.push(TAG_INTEGER);
+                self.bytes.extend_from_slice(&value.to_be_bytes());
+            },
+            Object::String(value) => {
+                self.bytes.push(TAG_STRING);
+                self.write_len(value.len())?;
+                self.bytes.extend_from_slice(value.as_bytes());
+            },
+            Object::CompiledFunction(function) => {
+                self.bytes.push(TAG_FUNCTION);
+                self.write_len(function.num_locals)?;
+                self.write_len(function.num_parameters)?;
+                self.write_code(&function.instructions, &function.positions)?;
+            },
+            other => return Err(format!("cannot serialize {} constant", other.type_name())),
+        }
+
+        Ok(())
+    }
+
+    fn write_code(&mut self, instructions: &[u8], positions: &[(usize, TokenRange)]) -> Result<(), MbcError> {
+        self.write_len(instructions.len())?;
+        self.bytes.extend_from_slice(instructions);
+
+        if self.debug {
+            self.write_len(positions.len())?;
+            for (offset, range) in positions {
+                self.write_len(*offset)?;
+                self.write_len(range.start)?;
+                self.write_len(range.end)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    debug: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], MbcError> {
+        let end = self.offset.checked_add(len).filter(|end| *end <= self.bytes.len());
+        match end {
+            Some(end) => {
+                let bytes = &self.bytes[self.offset..end];
+                self.offset = end;
+                Ok(bytes)
+            },
+            None => Err("unexpected end of bytecode file".to_string()),
+        }
+    }
+
+    fn read_u8(&mut self) -> Result<u8, MbcError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, MbcError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn read_u32(&mut self) -> Result<usize, MbcError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()) as usize)
+    }
+
+    fn read_constant(&mut self) -> Result<Object, MbcError> {
+        let tag = self.read_u8()?;
+
+        match tag {
+            TAG_INTEGER => Ok(Object::Integer(i64::from_be_bytes(self.take(8)?.try_into().unwrap()))),
+            TAG_STRING => {
+                let len = self.read_u32()?;
+                let bytes = self.take(len)?;
+                String::from_utf8(bytes.to_vec())
+                    .map(Object::String)
+                    .map_err(|_| "invalid utf-8 in string constant".to_string())
+            },
+            TAG_FUNCTION => {
+                let num_locals = self.read_u32()?;
+                let num_parameters = self.read_u32()?;
+                if num_parameters > num_locals {
+                    return Err(format!("function has {} parameters but {} locals", num_parameters, num_locals));
+                }
+                let (instructions, positions) = self.read_code()?;
+
+                Ok(Object::CompiledFunction(Rc::new(CompiledFunction {
+                    instructions,
+                    num_locals,
+                    num_parameters,
+                    positions,
+                })))
+            },
+            tag => Err(format!("unknown constant tag {}", tag)),
+        }
+    }
+
+    fn read_code(&mut self) -> Result<(Vec<u8>, Positions), MbcError> {
+        let len = self.read_u32()?;
+        let instructions = self.take(len)?.to_vec();
+
+        let mut positions = Vec::new();
+        if self.debug {
+            // entries are looked up by binary search, so their offsets have to be increasing
+            let count = self.read_u32()?;
+            for _ in 0..count {
+                let offset = self.read_u32()?;
+                let start = self.read_u32()?;
+                let end = self.read_u32()?;
+
+                if offset >= len {
+                    return Err(format!("position for offset {} is past the end of the code", offset));
+                }
+                if start > end {
+                    return Err(format!("position for offset {} ends before it starts", offset));
+                }
+                if matches!(positions.last(), Some((last, _)) if *last >= offset) {
+                    return Err(format!("position for offset {} is out of order", offset));
+                }
+                positions.push((offset, TokenRange { start, end }));
+            }
+        }
+
+        Ok((instructions, positions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::code::make;
+    use crate::compiler::compiler::compile_source;
+    use crate::vm::vm::VM;
+
+    #[test]
+    fn test_round_trip() {
+        let input = r#"
+            let greeting = "héllo";
+            let adder = fn(a) { fn(b) { a + b } };
+            let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } };
+            let early = fn(x) { if (x > 1) { return x; } let y = x * 2; };
+            let table = {"one": 1, true: [1, 2], 3: fn() { greeting }};
+            [greeting, adder(-1)(3), fib(10), len("abc"), early(5), early(1), table["one"], table[3](), !true]
+        "#;
+        let bytecode = compile_source(input, true).unwrap();
+
+        let decoded = decode(&encode(&bytecode, true).unwrap()).unwrap();
+        assert_eq!(decoded, bytecode);
+
+        let expected = VM::new(bytecode).run();
+        assert_eq!(VM::new(decoded).run(), expected);
+    }
+
+    #[test]
+    fn test_without_debug_info() {
+        let bytecode = compile_source("let f = fn(x) { x * 2 }; f(21)", true).unwrap();
+        let encoded = encode(&bytecode, false).unwrap();
+        let decoded = decode(&encoded).unwrap();
+
+        assert!(encoded.len() < encode(&bytecode, true).unwrap().len());
+        assert_eq!(decoded.instructions, bytecode.instructions);
+        assert!(decoded.positions.is_empty());
+        match &decoded.constants[1] {
+            Object::CompiledFunction(function) => assert!(function.positions.is_empty()),
+            constant => panic!("expected a compiled function, got {:?}", constant),
+        }
+        assert_eq!(VM::new(decoded).run(), Ok(Object::Integer(42)));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let valid = encode(&compile_source("1 + 2", true).unwrap(), false).unwrap();
+
+        let mut bad_version = valid.clone();
+        bad_version[5] = 9;
+        let mut trailing = valid.clone();
+        trailing.push(0);
+
+        let tests = vec![
+            (b"#!/bin/monkey".to_vec(), "not a Monkey bytecode file".to_string()),
+            (bad_version, format!("unsupported bytecode version 9, expected {}", VERSION)),
+            (valid[..valid.len() - 1].to_vec(), "unexpected end of bytecode file".to_string()),
+            (trailing, format!("unexpected data after the program at byte {}", valid.len())),
+        ];
+
+        for (bytes, expected) in tests {
+            assert_eq!(decode(&bytes), Err(expected));
+        }
+    }
+
+    #[test]
+    fn test_invalid_instructions() {
+        let tests = vec![
+            (vec![make(Opcode::Constant, &[1])], "invalid operand 1 for OpConstant at 0"),
+            (vec![make(Opcode::GetLocal, &[0])], "invalid operand 0 for OpGetLocal at 0"),
+            (vec![make(Opcode::Pop, &[]), vec![255]], "unknown opcode 255 at 1"),
+            (vec![vec![Opcode::Jump as u8, 0]], "truncated OpJump at 0"),
+            (vec![make(Opcode::Pop, &[])], "stack underflow in OpPop at 0"),
+            (vec![make(Opcode::Constant, &[0]), make(Opcode::Add, &[])], "stack underflow in OpAdd at 3"),
+            (vec![make(Opcode::Constant, &[0]), make(Opcode::Jump, &[1])], "invalid operand 1 for OpJump at 3"),
+            (vec![make(Opcode::Constant, &[0]), make(Opcode::Hash, &[1])], "invalid operand 1 for OpHash at 3"),
+            (
+                vec![
+                    make(Opcode::True, &[]),
+                    make(Opcode::JumpNotTruthy, &[7]),
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Pop, &[]),
+                ],
+                "inconsistent stack depth at 7",
+            ),
+        ];
+
+        for (instructions, expected) in tests {
+            let bytecode = Bytecode {
+                instructions: instructions.concat(),
+                constants: vec![Object::Integer(1)],
+                positions: Vec::new(),
+            };
+            assert_eq!(decode(&encode(&bytecode, false).unwrap()), Err(expected.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_invalid_positions() {
+        let range = |start, end| TokenRange { start, end };
+        let tests = vec![
+            (vec![(3, range(0, 1))], "position for offset 3 is past the end of the code"),
+            (vec![(0, range(2, 1))], "position for offset 0 ends before it starts"),
+            (vec![(1, range(0, 1)), (1, range(1, 2))], "position for offset 1 is out of order"),
+            (vec![(1, range(0, 1)), (0, range(1, 2))], "position for offset 0 is out of order"),
+        ];
+
+        for (positions, expected) in tests {
+            let bytecode = Bytecode {
+                instructions: [make(Opcode::True, &[]), make(Opcode::Pop, &[]), make(Opcode::Null, &[])].concat(),
+                constants: Vec::new(),
+                positions,
+            };
+            assert_eq!(decode(&encode(&bytecode, true).unwrap()), Err(expected.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_invalid_functions() {
+        let tests = vec![
+            (vec![make(Opcode::GetFree, &[0]), make(Opcode::ReturnValue, &[])], 0, "invalid operand 0 for OpGetFree at 0"),
+            (vec![make(Opcode::GetFree, &[1]), make(Opcode::ReturnValue, &[])], 1, "invalid operand 1 for OpGetFree at 0"),
+            (vec![make(Opcode::Null, &[])], 0, "missing return at the end of a function"),
+            (vec![make(Opcode::ReturnValue, &[])], 0, "stack underflow in OpReturnValue at 0"),
+        ];
+
+        for (instructions, num_free, expected) in tests {
+            let function = CompiledFunction {
+                instructions: instructions.concat(),
+                num_locals: 0,
+                num_parameters: 0,
+                positions: Vec::new(),
+            };
+            let main = [vec![Opcode::Null as u8; num_free], make(Opcode::Closure, &[0, num_free]), make(Opcode::Pop, &[])];
+            let bytecode = Bytecode {
+                instructions: main.concat(),
+                constants: vec![Object::CompiledFunction(Rc::new(function))],
+                positions: Vec::new(),
+            };
+            assert_eq!(decode(&encode(&bytecode, false).unwrap()), Err(expected.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_unsupported_constants() {
+        let bytecode = Bytecode {
+            instructions: Vec::new(),
+            constants: vec![Object::Null],
+            positions: Vec::new(),
+        };
+
+        assert_eq!(encode(&bytecode, false), Err("cannot serialize NULL constant".to_string()));
+    }
+}
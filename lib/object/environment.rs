@@ -0,0 +1,91 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use super::object::Object;
+
+pub type Env = Rc<RefCell<Environment>>;
+
+#[derive(Debug, Default)]
+pub struct Environment {
+    store: HashMap<String, Object>,
+    outer: Option<Env>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Environment {
+            store: HashMap::new(),
+            outer: None,
+        }
+    }
+
+    // a scope whose lookups fall back to `outer`, e.g. a function body closing over where it was defined
+    pub fn new_enclosed(outer: Env) -> Self {
+        Environment {
+            store: HashMap::new(),
+            outer: Some(outer),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Object> {
+        match self.store.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.outer.as_ref()?.borrow().get(name),
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: Object) {
+        self.store.insert(name.to_string(), value);
+    }
+
+    // drops every binding in this scope; functions keep the scope they were defined in alive, so
+    // this is how a scope that holds functions gets freed
+    pub fn clear(&mut self) {
+        self.store.clear();
+    }
+
+    // every name visible from this scope, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.store.keys().cloned().collect();
+        if let Some(outer) = &self.outer {
+            names.extend(outer.borrow().names());
+        }
+        names.sort();
+        names.dedup();
+
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_set() {
+        let mut env = Environment::new();
+        env.set("x", Object::Integer(5));
+
+        assert_eq!(env.get("x"), Some(Object::Integer(5)));
+        assert_eq!(env.get("y"), None);
+    }
+
+    #[test]
+    fn test_enclosed_environment() {
+        let outer = Rc::new(RefCell::new(Environment::new()));
+        outer.borrow_mut().set("x", Object::Integer(5));
+        outer.borrow_mut().set("y", Object::Integer(10));
+
+        let mut inner = Environment::new_enclosed(Rc::clone(&outer));
+        inner.set("x", Object::Boolean(true));
+
+        assert_eq!(inner.get("x"), Some(Object::Boolean(true)));
+        assert_eq!(inner.get("y"), Some(Object::Integer(10)));
+        assert_eq!(outer.borrow().get("x"), Some(Object::Integer(5)));
+
+        // bindings added to the outer scope after capture are still visible
+        outer.borrow_mut().set("z", Object::Null);
+        assert_eq!(inner.get("z"), Some(Object::Null));
+
+        assert_eq!(inner.names(), vec!["x", "y", "z"]);
+    }
+}
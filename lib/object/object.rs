@@ -0,0 +1,236 @@
+use core::fmt;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::code::code::{Instructions, Positions};
+use crate::parser::ast::{BlockStatement, Identifier};
+
+use super::environment::Env;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+    Array(Vec<Object>),
+    Hash(BTreeMap<HashKey, Object>),
+    ReturnValue(Box<Object>),
+    Error(String),
+    Function(Rc<Function>),
+    Builtin(Builtin),
+    HostFunction(Rc<HostFunction>),
+    CompiledFunction(Rc<CompiledFunction>),
+    Closure(Rc<Closure>),
+    Null,
+}
+
+// a function literal lowered by the compiler; num_locals includes the parameters, and positions is
+// empty when the function was loaded without debug information
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledFunction {
+    pub instructions: Instructions,
+    pub num_locals: usize,
+    pub num_parameters: usize,
+    pub positions: Positions,
+}
+
+// the runtime value of a function literal in the VM: the compiled function together with the
+// values of the free variables it captured
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub free: Vec<Object>,
+}
+
+// like evaluator functions, closures are compared by identity
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+pub type BuiltinFunction = fn(Vec<Object>) -> Object;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub function: BuiltinFunction,
+}
+
+// builtins are registered once by name, so the name identifies them
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+// only integers, booleans and strings can key a hash; the key is kept in this form and turned
+// back into an Object when needed, so pairs don't store the key twice
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+}
+
+impl From<HashKey> for Object {
+    fn from(key: HashKey) -> Self {
+        match key {
+            HashKey::Integer(value) => Object::Integer(value),
+            HashKey::Boolean(value) => Object::Boolean(value),
+            HashKey::String(value) => Object::String(value),
+        }
+    }
+}
+
+// a native function registered at runtime by an embedding application, see interpreter::Interpreter
+pub struct HostFunction {
+    pub name: String,
+    pub function: Box<dyn Fn(Vec<Object>) -> Object>,
+}
+
+impl PartialEq for HostFunction {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostFunction")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+pub struct Function {
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+    pub env: Env,
+}
+
+// functions are compared by identity, and their environment is left out of Debug since a
+// closure's environment usually holds the closure itself
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("parameters", &self.parameters)
+            .field("body", &self.body)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parameters: Vec<&str> = self.parameters.iter().map(|p| p.name.as_str()).collect();
+        write!(f, "fn({}) {}", parameters.join(", "), self.body)
+    }
+}
+
+impl Object {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => "INTEGER",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::String(_) => "STRING",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::ReturnValue(_) => "RETURN_VALUE",
+            Object::Error(_) => "ERROR",
+            Object::Function(_) | Object::Closure(_) => "FUNCTION",
+            Object::Builtin(_) | Object::HostFunction(_) => "BUILTIN",
+            Object::CompiledFunction(_) => "COMPILED_FUNCTION",
+            Object::Null => "NULL",
+        }
+    }
+
+    pub fn hash_key(&self) -> Option<HashKey> {
+        match self {
+            Object::Integer(value) => Some(HashKey::Integer(*value)),
+            Object::Boolean(value) => Some(HashKey::Boolean(*value)),
+            Object::String(value) => Some(HashKey::String(value.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Object::Error(_))
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Object::Integer(value) => write!(f, "{}", value),
+            Object::Boolean(value) => write!(f, "{}", value),
+            Object::String(value) => write!(f, "{}", value),
+            Object::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            },
+            Object::Hash(pairs) => {
+                let pairs: Vec<String> = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", Object::from(key.clone()), value))
+                    .collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            },
+            Object::ReturnValue(value) => write!(f, "{}", value),
+            Object::Error(message) => write!(f, "ERROR: {}", message),
+            Object::Function(function) => write!(f, "{}", function),
+            Object::Builtin(builtin) => write!(f, "builtin function {}", builtin.name),
+            Object::HostFunction(function) => write!(f, "builtin function {}", function.name),
+            Object::CompiledFunction(function) => {
+                write!(f, "compiled function/{} ({} bytes)", function.num_parameters, function.instructions.len())
+            },
+            Object::Closure(closure) => write!(f, "closure/{}", closure.function.num_parameters),
+            Object::Null => write!(f, "null"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect() {
+        let tests = vec![
+            (Object::Integer(-42), "-42"),
+            (Object::Boolean(true), "true"),
+            (Object::String("hello world".to_string()), "hello world"),
+            (Object::Array(vec![Object::Integer(1), Object::Boolean(false)]), "[1, false]"),
+            (
+                Object::Hash(BTreeMap::from([
+                    (HashKey::String("name".to_string()), Object::String("monkey".to_string())),
+                    (HashKey::Integer(1), Object::Boolean(true)),
+                ])),
+                "{1: true, name: monkey}",
+            ),
+            (Object::Null, "null"),
+            (Object::ReturnValue(Box::new(Object::Integer(5))), "5"),
+            (Object::Error("unknown operator: -BOOLEAN".to_string()), "ERROR: unknown operator: -BOOLEAN"),
+        ];
+
+        for (object, expected) in tests {
+            assert_eq!(object.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_hash_keys() {
+        let hello = Object::String("Hello World".to_string());
+
+        assert_eq!(hello.hash_key(), Object::String("Hello World".to_string()).hash_key());
+        assert_ne!(hello.hash_key(), Object::String("My name is johnny".to_string()).hash_key());
+        assert_ne!(Object::Integer(1).hash_key(), Object::Boolean(true).hash_key());
+        assert_eq!(Object::Null.hash_key(), None);
+        assert_eq!(Object::Array(vec![]).hash_key(), None);
+    }
+}
@@ -1,3 +1,5 @@
+use core::fmt;
+
 use crate::token::token::{Token, TokenRange, TokenEnum};
 
 pub enum Node {
@@ -15,7 +17,7 @@ pub enum Statement {
 
 #[derive(Debug, Clone)]
 pub struct LetStatement {
-    pub token: Token,
+    pub identifier: Identifier,
     pub expression: Expression,
     pub range: TokenRange,
 }
@@ -26,17 +28,31 @@ pub struct ReturnStatement {
     pub range: TokenRange,
 }
 
+#[derive(Debug, Clone)]
+pub struct BlockStatement {
+    pub statements: Vec<Statement>,
+    pub range: TokenRange,
+}
+
 #[derive(Debug, Clone)]
 pub enum Expression {
     Identifier(Identifier),
     Literal(Literal),
     Prefix(Prefix),
     Infix(Infix),
+    If(If),
+    Function(Function),
+    Call(Call),
+    Index(Index),
+    Hash(Hash),
 }
 
 #[derive(Debug, Clone)]
 pub enum Literal {
     Integer(Integer),
+    Boolean(Boolean),
+    String(StringLiteral),
+    Array(Array),
 }
 
 #[derive(Debug, Clone)]
@@ -54,9 +70,63 @@ pub struct Infix {
     pub range: TokenRange,
 }
 
+// an `else if` chain is stored as an alternative block holding the nested `If` expression
+#[derive(Debug, Clone)]
+pub struct If {
+    pub condition: Box<Expression>,
+    pub consequence: BlockStatement,
+    pub alternative: Option<BlockStatement>,
+    pub range: TokenRange,
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+    pub range: TokenRange,
+}
+
+#[derive(Debug, Clone)]
+pub struct Call {
+    pub function: Box<Expression>,
+    pub arguments: Vec<Expression>,
+    pub range: TokenRange,
+}
+
+#[derive(Debug, Clone)]
+pub struct Index {
+    pub left: Box<Expression>,
+    pub index: Box<Expression>,
+    pub range: TokenRange,
+}
+
+#[derive(Debug, Clone)]
+pub struct Hash {
+    pub pairs: Vec<(Expression, Expression)>,
+    pub range: TokenRange,
+}
+
 #[derive(Debug, Clone)]
 pub struct Integer {
-    pub value: u32,
+    pub value: i64,
+    pub range: TokenRange,
+}
+
+#[derive(Debug, Clone)]
+pub struct Boolean {
+    pub value: bool,
+    pub range: TokenRange,
+}
+
+#[derive(Debug, Clone)]
+pub struct StringLiteral {
+    pub value: String,
+    pub range: TokenRange,
+}
+
+#[derive(Debug, Clone)]
+pub struct Array {
+    pub elements: Vec<Expression>,
     pub range: TokenRange,
 }
 
@@ -66,10 +136,117 @@ pub struct Identifier {
     pub range: TokenRange,
 }
 
+#[derive(Debug, Clone)]
 pub struct Program {
     pub statements: Vec<Statement>,
 }
 
+impl Statement {
+    pub fn range(&self) -> TokenRange {
+        match self {
+            Statement::LetStatement(statement) => statement.range,
+            Statement::ReturnStatement(statement) => statement.range,
+            Statement::Expression(expression) => expression.range(),
+        }
+    }
+}
+
+impl Expression {
+    pub fn range(&self) -> TokenRange {
+        match self {
+            Expression::Identifier(identifier) => identifier.range,
+            Expression::Literal(Literal::Integer(integer)) => integer.range,
+            Expression::Literal(Literal::Boolean(boolean)) => boolean.range,
+            Expression::Literal(Literal::String(string)) => string.range,
+            Expression::Literal(Literal::Array(array)) => array.range,
+            Expression::Prefix(prefix) => prefix.range,
+            Expression::Infix(infix) => infix.range,
+            Expression::If(if_expression) => if_expression.range,
+            Expression::Function(function) => function.range,
+            Expression::Call(call) => call.range,
+            Expression::Index(index) => index.range,
+            Expression::Hash(hash) => hash.range,
+        }
+    }
+}
+
+// the Display impls render a fully parenthesised form of the tree, which is what the parser tests
+// compare precedence against
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for statement in &self.statements {
+            write!(f, "{}", statement)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Statement::LetStatement(statement) => write!(f, "let {} = {};", statement.identifier.name, statement.expression),
+            Statement::ReturnStatement(statement) => write!(f, "return {};", statement.expression),
+            Statement::Expression(expression) => write!(f, "{}", expression),
+        }
+    }
+}
+
+impl fmt::Display for BlockStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let statements: Vec<String> = self.statements.iter().map(|s| s.to_string()).collect();
+        if statements.is_empty() {
+            write!(f, "{{}}")
+        } else {
+            write!(f, "{{ {} }}", statements.join(" "))
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Identifier(identifier) => write!(f, "{}", identifier.name),
+            Expression::Literal(literal) => write!(f, "{}", literal),
+            Expression::Prefix(prefix) => write!(f, "({}{})", prefix.token.token_type, prefix.expression),
+            Expression::Infix(infix) => write!(f, "({} {} {})", infix.left, infix.token.token_type, infix.right),
+            Expression::If(if_expression) => {
+                write!(f, "if {} {}", if_expression.condition, if_expression.consequence)?;
+                if let Some(alternative) = &if_expression.alternative {
+                    write!(f, " else {}", alternative)?;
+                }
+                Ok(())
+            }
+            Expression::Function(function) => {
+                let parameters: Vec<&str> = function.parameters.iter().map(|p| p.name.as_str()).collect();
+                write!(f, "fn({}) {}", parameters.join(", "), function.body)
+            }
+            Expression::Call(call) => {
+                let arguments: Vec<String> = call.arguments.iter().map(|a| a.to_string()).collect();
+                write!(f, "{}({})", call.function, arguments.join(", "))
+            }
+            Expression::Index(index) => write!(f, "({}[{}])", index.left, index.index),
+            Expression::Hash(hash) => {
+                let pairs: Vec<String> = hash.pairs.iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Integer(integer) => write!(f, "{}", integer.value),
+            Literal::Boolean(boolean) => write!(f, "{}", boolean.value),
+            Literal::String(string) => write!(f, "{:?}", string.value),
+            Literal::Array(array) => {
+                let elements: Vec<String> = array.elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
 pub enum Precedence {
     LOWEST,
@@ -93,6 +270,7 @@ pub fn get_precedence(token: &TokenEnum) -> Precedence {
         TokenEnum::ASTERISK => Precedence::PRODUCT,
         TokenEnum::SLASH => Precedence::PRODUCT,
         TokenEnum::LPAREN => Precedence::CALL,
+        TokenEnum::LBRACKET => Precedence::INDEX,
         _ => Precedence::LOWEST,
     }
 }
@@ -1,15 +1,97 @@
-use crate::{lexer::lexer::Lexer, token::token::{Token, TokenEnum, TokenRange}};
+use core::fmt;
 
-use super::ast::{Program, Statement, LetStatement, Literal, Expression, Identifier, ReturnStatement, Precedence, Integer, get_precedence, Infix};
+use crate::{lexer::lexer::Lexer, token::token::{Illegal, Token, TokenEnum, TokenRange}};
 
-type ParsingError = String;
-type ParsingErrors = Vec<ParsingError>;
+use super::ast::{Program, Statement, LetStatement, Literal, Expression, Identifier, ReturnStatement, Precedence, Integer, get_precedence, Infix, BlockStatement, Function, Call, If, Boolean, Prefix, StringLiteral, Array, Index, Hash};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    // a token other than the ones the grammar allows at that point
+    UnexpectedToken,
+    // a token that can't start an expression
+    ExpectedExpression,
+    // a function parameter that isn't an identifier
+    ExpectedParameter,
+    // input the lexer couldn't make a token of, such as an unterminated string; `found` holds
+    // the reason
+    IllegalToken,
+}
+
+impl ParseErrorKind {
+    // stable identifiers tools can match on without depending on the wording of messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseErrorKind::UnexpectedToken => "E0001",
+            ParseErrorKind::ExpectedExpression => "E0002",
+            ParseErrorKind::ExpectedParameter => "E0003",
+            ParseErrorKind::IllegalToken => "E0004",
+        }
+    }
+}
+
+// a parse failure at the token `found`, which spans `range`; `expected` holds the tokens that
+// would have been accepted instead, with identifiers, integers and strings standing for any
+// value of their kind
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub range: TokenRange,
+    pub found: TokenEnum,
+    pub expected: Vec<TokenEnum>,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let found = self.found.to_string();
+        let expected = self.expected.iter().map(describe_expected).collect::<Vec<String>>().join(" or ");
+
+        match self.kind {
+            ParseErrorKind::UnexpectedToken => write!(f, "expected next token to be {}, got {:?} instead.", expected, found),
+            ParseErrorKind::ExpectedExpression => write!(f, "no prefix parse function for {:?} found.", found),
+            ParseErrorKind::ExpectedParameter => write!(f, "expected parameter to be {}, got {:?} instead.", expected, found),
+            ParseErrorKind::IllegalToken => match self.found {
+                TokenEnum::ILLEGAL(Illegal::Character(ch)) => write!(f, "illegal character {:?}.", ch),
+                TokenEnum::ILLEGAL(Illegal::UnterminatedString) => write!(f, "unterminated string literal."),
+                TokenEnum::ILLEGAL(Illegal::InvalidEscape) => write!(f, "invalid escape sequence in string literal."),
+                TokenEnum::ILLEGAL(Illegal::IntegerOutOfRange) => write!(f, "integer literal out of range."),
+                _ => write!(f, "illegal token {:?}.", found),
+            },
+        }
+    }
+}
+
+fn describe_expected(token: &TokenEnum) -> String {
+    match token {
+        TokenEnum::IDENT { .. } => "IDENT".to_string(),
+        TokenEnum::INT(_) => "INT".to_string(),
+        TokenEnum::STRING(_) => "STRING".to_string(),
+        token => format!("{:?}", token.to_string()),
+    }
+}
+
+// the tokens parse_prefix_expression can start an expression with
+const EXPRESSION_START: &[TokenEnum] = &[
+    TokenEnum::IDENT { name: String::new() },
+    TokenEnum::INT(0),
+    TokenEnum::STRING(String::new()),
+    TokenEnum::TRUE,
+    TokenEnum::FALSE,
+    TokenEnum::BANG,
+    TokenEnum::MINUS,
+    TokenEnum::LPAREN,
+    TokenEnum::LBRACKET,
+    TokenEnum::LBRACE,
+    TokenEnum::IF,
+    TokenEnum::FUNCTION,
+];
+
+type ParseErrors = Vec<ParseError>;
 
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
     current_token: Token,
     peek_token: Token,
-    errors: ParsingErrors,
+    errors: ParseErrors,
 }
 
 impl<'a> Parser<'a> {
@@ -27,6 +109,29 @@ impl<'a> Parser<'a> {
         }
     }
 
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    // an illegal token is reported as such wherever it turns up, since it is the actual mistake
+    fn error(&mut self, kind: ParseErrorKind, token: Token, expected: Vec<TokenEnum>) {
+        let kind = match token.token_type {
+            TokenEnum::ILLEGAL(_) => ParseErrorKind::IllegalToken,
+            _ => kind,
+        };
+
+        self.errors.push(ParseError {
+            kind,
+            range: token.range,
+            found: token.token_type,
+            expected,
+        });
+    }
+
+    fn peek_error(&mut self, expected: Vec<TokenEnum>) {
+        self.error(ParseErrorKind::UnexpectedToken, self.peek_token.clone(), expected);
+    }
+
     pub fn next_token(&mut self) {
         self.current_token = self.peek_token.clone();
         self.peek_token = self.lexer.next_token();
@@ -37,7 +142,6 @@ impl<'a> Parser<'a> {
     }
 
     pub fn peek_token_is(&self, token_type: &TokenEnum) -> bool {
-        println!("peek_token type {:?}", self.peek_token.token_type);
         self.peek_token.token_type == *token_type
     }
 
@@ -46,41 +150,46 @@ impl<'a> Parser<'a> {
             self.next_token();
             true
         } else {
-            let msg = format!(
-                "expected next token to be {:?}, got {:?} instead.", 
-                (*token_type).to_string(), 
-                self.peek_token.token_type.to_string()
-            );
-            self.errors.push(msg);
+            self.peek_error(vec![token_type.clone()]);
             false
         }
     }
 
-    pub fn parse_program(&mut self) -> Program {
+    // every statement is parsed, so all the errors in the input are reported rather than just the
+    // first
+    pub fn parse_program(&mut self) -> Result<Program, ParseErrors> {
         let mut statements: Vec<Statement> = Vec::new();
 
         while self.current_token.token_type != TokenEnum::EOF {
-            if let Some(statement) = self.parse_statement() {
-                statements.push(statement);
-            } else {
-                println!("didn't get the statement");
+            match self.parse_statement() {
+                Some(statement) => statements.push(statement),
+                None => self.skip_to_statement_end(),
             }
             self.next_token();
         }
 
-        Program {
-            statements,
+        if !self.errors.is_empty() {
+            return Err(self.errors.clone());
         }
+
+        Ok(Program {
+            statements,
+        })
     }
 
     pub fn parse_statement(&mut self) -> Option<Statement> {
         match &self.current_token.token_type {
             TokenEnum::LET => self.parse_let_statement(),
             TokenEnum::RETURN => self.parse_return_statement(),
-            _ => {
-                println!("missing token type {:?}", self.current_token.token_type);
-                None
-            },
+            _ => self.parse_expression_statement(),
+        }
+    }
+
+    // after a failed statement, resume at the next `;` so one mistake doesn't cascade into
+    // errors for every remaining token of that statement
+    fn skip_to_statement_end(&mut self) {
+        while !self.current_token_is(TokenEnum::SEMICOLON) && !self.current_token_is(TokenEnum::EOF) {
+            self.next_token();
         }
     }
 
@@ -95,27 +204,11 @@ impl<'a> Parser<'a> {
     }
 
     pub fn parse_expression(&mut self, precedence: Precedence) -> Option<(Expression, TokenRange)> {
-        let mut left_start = self.current_token.range.start.clone();
+        let left_start = self.current_token.range.start;
         let mut left = self.parse_prefix_expression()?;
 
-        while self.peek_token_is(&TokenEnum::SEMICOLON) && precedence < get_precedence(&self.peek_token.token_type) {
-            match self.parse_infix_expression(&left, left_start) {
-                Some(infix) => {
-                    left = infix;
-                    if let Expression::Infix(infix_expr) = left.clone() {
-                        left_start = infix_expr.range.start;
-                    }
-                },
-                None => {
-                    return Some((
-                        left,
-                        TokenRange {
-                            start: left_start,
-                            end: self.current_token.range.end
-                        }
-                    ))
-                }
-            }
+        while !self.peek_token_is(&TokenEnum::SEMICOLON) && precedence < get_precedence(&self.peek_token.token_type) {
+            left = self.parse_infix_expression(&left, left_start)?;
         }
 
         let end = self.current_token.range.end;
@@ -143,38 +236,302 @@ impl<'a> Parser<'a> {
                 let infix_op = self.current_token.clone();
                 let precedence_value = get_precedence(&self.current_token.token_type);
                 self.next_token();
-                let (right, span) = self.parse_expression(precedence_value).unwrap();
-                return Some(Expression::Infix(Infix {
+                let (right, span) = self.parse_expression(precedence_value)?;
+                Some(Expression::Infix(Infix {
                     token: infix_op,
                     left: Box::new(expression.clone()),
                     right: Box::new(right),
                     range: TokenRange { start: from, end: span.end },
-                }));
+                }))
+            }
+            TokenEnum::LPAREN => {
+                self.next_token();
+                let arguments = self.parse_expression_list(&TokenEnum::RPAREN)?;
+                Some(Expression::Call(Call {
+                    function: Box::new(expression.clone()),
+                    arguments,
+                    range: TokenRange { start: from, end: self.current_token.range.end },
+                }))
+            }
+            TokenEnum::LBRACKET => {
+                self.next_token();
+                self.next_token();
+                let (index, _) = self.parse_expression(Precedence::LOWEST)?;
+
+                if !self.expect_peek(&TokenEnum::RBRACKET) {
+                    return None;
+                }
+
+                Some(Expression::Index(Index {
+                    left: Box::new(expression.clone()),
+                    index: Box::new(index),
+                    range: TokenRange { start: from, end: self.current_token.range.end },
+                }))
             }
             _ => None,
         }
     }
 
+    fn parse_expression_list(&mut self, end: &TokenEnum) -> Option<Vec<Expression>> {
+        let mut expressions = Vec::new();
+
+        if self.peek_token_is(end) {
+            self.next_token();
+            return Some(expressions);
+        }
+
+        self.next_token();
+        expressions.push(self.parse_expression(Precedence::LOWEST)?.0);
+
+        while self.peek_token_is(&TokenEnum::COMMA) {
+            self.next_token();
+            self.next_token();
+            expressions.push(self.parse_expression(Precedence::LOWEST)?.0);
+        }
+
+        if !self.peek_token_is(end) {
+            self.peek_error(vec![TokenEnum::COMMA, end.clone()]);
+            return None;
+        }
+        self.next_token();
+
+        Some(expressions)
+    }
+
     pub fn parse_prefix_expression(&mut self) -> Option<Expression> {
         match &self.current_token.token_type {
             TokenEnum::IDENT { name } => {
-                return Some(Expression::Identifier(Identifier {
+                Some(Expression::Identifier(Identifier {
                     name: name.clone(),
                     range: self.current_token.clone().range,
                 }))
             }
             TokenEnum::INT(i) => {
-                return Some(Expression::Literal(Literal::Integer(Integer {
+                Some(Expression::Literal(Literal::Integer(Integer {
                     value: *i,
                     range: self.current_token.clone().range,
                 })))
             }
+            TokenEnum::STRING(value) => {
+                Some(Expression::Literal(Literal::String(StringLiteral {
+                    value: value.clone(),
+                    range: self.current_token.range,
+                })))
+            }
+            TokenEnum::LBRACKET => {
+                let start = self.current_token.range.start;
+                let elements = self.parse_expression_list(&TokenEnum::RBRACKET)?;
+
+                Some(Expression::Literal(Literal::Array(Array {
+                    elements,
+                    range: TokenRange { start, end: self.current_token.range.end },
+                })))
+            }
+            TokenEnum::LBRACE => self.parse_hash_literal(),
+            TokenEnum::TRUE | TokenEnum::FALSE => {
+                Some(Expression::Literal(Literal::Boolean(Boolean {
+                    value: self.current_token_is(TokenEnum::TRUE),
+                    range: self.current_token.range,
+                })))
+            }
+            TokenEnum::BANG | TokenEnum::MINUS => {
+                let token = self.current_token.clone();
+                self.next_token();
+                let (expression, span) = self.parse_expression(Precedence::PREFIX)?;
+
+                Some(Expression::Prefix(Prefix {
+                    range: TokenRange { start: token.range.start, end: span.end },
+                    token,
+                    expression: Box::new(expression),
+                }))
+            }
+            TokenEnum::LPAREN => {
+                self.next_token();
+                let (expression, _) = self.parse_expression(Precedence::LOWEST)?;
+
+                if !self.expect_peek(&TokenEnum::RPAREN) {
+                    return None;
+                }
+
+                Some(expression)
+            }
+            TokenEnum::IF => self.parse_if_expression().map(Expression::If),
+            TokenEnum::FUNCTION => self.parse_function_literal(),
             _ => {
+                self.error(ParseErrorKind::ExpectedExpression, self.current_token.clone(), EXPRESSION_START.to_vec());
                 None
             }
         }
     }
 
+    fn parse_hash_literal(&mut self) -> Option<Expression> {
+        let start = self.current_token.range.start;
+        let mut pairs = Vec::new();
+
+        while !self.peek_token_is(&TokenEnum::RBRACE) {
+            self.next_token();
+            let (key, _) = self.parse_expression(Precedence::LOWEST)?;
+
+            if !self.expect_peek(&TokenEnum::COLON) {
+                return None;
+            }
+
+            self.next_token();
+            let (value, _) = self.parse_expression(Precedence::LOWEST)?;
+            pairs.push((key, value));
+
+            if !self.peek_token_is(&TokenEnum::RBRACE) {
+                if !self.peek_token_is(&TokenEnum::COMMA) {
+                    self.peek_error(vec![TokenEnum::COMMA, TokenEnum::RBRACE]);
+                    return None;
+                }
+                self.next_token();
+            }
+        }
+
+        self.next_token();
+
+        Some(Expression::Hash(Hash {
+            pairs,
+            range: TokenRange { start, end: self.current_token.range.end },
+        }))
+    }
+
+    fn parse_if_expression(&mut self) -> Option<If> {
+        let start = self.current_token.range.start;
+
+        if !self.expect_peek(&TokenEnum::LPAREN) {
+            return None;
+        }
+
+        self.next_token();
+        let (condition, _) = self.parse_expression(Precedence::LOWEST)?;
+
+        if !self.expect_peek(&TokenEnum::RPAREN) || !self.expect_peek(&TokenEnum::LBRACE) {
+            return None;
+        }
+
+        let consequence = self.parse_block_statement()?;
+        let mut alternative = None;
+
+        if self.peek_token_is(&TokenEnum::ELSE) {
+            self.next_token();
+
+            if self.peek_token_is(&TokenEnum::IF) {
+                self.next_token();
+                let nested = self.parse_if_expression()?;
+                alternative = Some(BlockStatement {
+                    range: nested.range,
+                    statements: vec![Statement::Expression(Expression::If(nested))],
+                });
+            } else {
+                if !self.expect_peek(&TokenEnum::LBRACE) {
+                    return None;
+                }
+                alternative = Some(self.parse_block_statement()?);
+            }
+        }
+
+        let end = match &alternative {
+            Some(block) => block.range.end,
+            None => consequence.range.end,
+        };
+
+        Some(If {
+            condition: Box::new(condition),
+            consequence,
+            alternative,
+            range: TokenRange { start, end },
+        })
+    }
+
+    fn parse_function_literal(&mut self) -> Option<Expression> {
+        let start = self.current_token.range.start;
+
+        if !self.expect_peek(&TokenEnum::LPAREN) {
+            return None;
+        }
+
+        let parameters = self.parse_function_parameters()?;
+
+        if !self.expect_peek(&TokenEnum::LBRACE) {
+            return None;
+        }
+
+        let body = self.parse_block_statement()?;
+        let end = body.range.end;
+
+        Some(Expression::Function(Function {
+            parameters,
+            body,
+            range: TokenRange { start, end },
+        }))
+    }
+
+    fn parse_function_parameters(&mut self) -> Option<Vec<Identifier>> {
+        let mut parameters = Vec::new();
+
+        if self.peek_token_is(&TokenEnum::RPAREN) {
+            self.next_token();
+            return Some(parameters);
+        }
+
+        self.next_token();
+        parameters.push(self.parse_parameter()?);
+
+        while self.peek_token_is(&TokenEnum::COMMA) {
+            self.next_token();
+            self.next_token();
+            parameters.push(self.parse_parameter()?);
+        }
+
+        if !self.peek_token_is(&TokenEnum::RPAREN) {
+            self.peek_error(vec![TokenEnum::COMMA, TokenEnum::RPAREN]);
+            return None;
+        }
+        self.next_token();
+
+        Some(parameters)
+    }
+
+    fn parse_parameter(&mut self) -> Option<Identifier> {
+        match &self.current_token.token_type {
+            TokenEnum::IDENT { name } => Some(Identifier {
+                name: name.clone(),
+                range: self.current_token.range,
+            }),
+            _ => {
+                let expected = vec![TokenEnum::IDENT { name: String::new() }];
+                self.error(ParseErrorKind::ExpectedParameter, self.current_token.clone(), expected);
+                None
+            }
+        }
+    }
+
+    pub fn parse_block_statement(&mut self) -> Option<BlockStatement> {
+        let start = self.current_token.range.start;
+        let mut statements = Vec::new();
+
+        self.next_token();
+
+        while !self.current_token_is(TokenEnum::RBRACE) {
+            if self.current_token_is(TokenEnum::EOF) {
+                self.error(ParseErrorKind::UnexpectedToken, self.current_token.clone(), vec![TokenEnum::RBRACE]);
+                return None;
+            }
+
+            if let Some(statement) = self.parse_statement() {
+                statements.push(statement);
+            }
+            self.next_token();
+        }
+
+        Some(BlockStatement {
+            statements,
+            range: TokenRange { start, end: self.current_token.range.end },
+        })
+    }
+
     pub fn parse_return_statement(&mut self) -> Option<Statement> {
         let start = self.current_token.range.start;
         self.next_token();
@@ -186,54 +543,45 @@ impl<'a> Parser<'a> {
         }
         let end = self.current_token.range.end;
 
-        return Some(Statement::ReturnStatement(ReturnStatement {
+        Some(Statement::ReturnStatement(ReturnStatement {
             expression: value,
             range: TokenRange { start, end },
-        }));
+        }))
     }
 
     pub fn parse_let_statement(&mut self) -> Option<Statement> {
         let start = self.current_token.range.start;
         self.next_token();
 
-        let current_token = self.current_token.clone();
-        let mut identifier = "".to_string();
-
-        match &self.current_token.token_type {
-            TokenEnum::IDENT { name } => {
-                identifier = name.to_string()
+        let identifier = match &self.current_token.token_type {
+            TokenEnum::IDENT { name } => Identifier {
+                name: name.to_string(),
+                range: self.current_token.range,
             },
             _ => {
-                self.errors.push(format!("expected next token to be IDENT, got {:?} instead.", self.current_token.token_type.to_string()));
+                let expected = vec![TokenEnum::IDENT { name: String::new() }];
+                self.error(ParseErrorKind::UnexpectedToken, self.current_token.clone(), expected);
                 return None;
             },
-        }
+        };
 
-        let expression = Expression::Identifier(
-            Identifier { 
-                name: identifier, 
-                range: current_token.range 
-            }
-        );
+        if !self.expect_peek(&TokenEnum::ASSIGN) {
+            return None;
+        }
+        self.next_token();
 
-        self.expect_peek(&TokenEnum::ASSIGN);
+        let (expression, _) = self.parse_expression(Precedence::LOWEST)?;
 
-        while !self.current_token_is(TokenEnum::SEMICOLON) {
+        if self.peek_token_is(&TokenEnum::SEMICOLON) {
             self.next_token();
         }
-
         let end = self.current_token.range.end;
 
-        let statement = LetStatement {
-            token: self.current_token.clone(),
+        Some(Statement::LetStatement(LetStatement {
+            identifier,
             expression,
-            range: TokenRange {
-                start,
-                end,
-            },
-        };
-
-        Some(Statement::LetStatement(statement))
+            range: TokenRange { start, end },
+        }))
     }
 }
 
@@ -250,7 +598,7 @@ mod test {
         ");
 
         let mut parser = Parser::new(lexer);
-        let program = parser.parse_program();
+        let program = parser.parse_program().unwrap();
 
         assert_eq!(program.statements.len(), 3);
     }
@@ -264,9 +612,10 @@ mod test {
         ");
 
         let mut parser = Parser::new(lexer);
-        let _ = parser.parse_program();
+        let errors = parser.parse_program().unwrap_err();
 
-        assert_eq!(parser.errors.len(), 3);
+        assert_eq!(errors.len(), 3);
+        assert_eq!(parser.errors(), errors.as_slice());
     }
 
     #[test]
@@ -278,9 +627,420 @@ mod test {
         ");
 
         let mut parser = Parser::new(lexer);
-        let program = parser.parse_program();
+        let program = parser.parse_program().unwrap();
 
         println!("statements {:?}", program.statements);
         assert_eq!(program.statements.len(), 3);
     }
+
+    #[test]
+    fn test_function_literal() {
+        let lexer = Lexer::new("return fn(x, y) { return x + y; };");
+
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+        assert_eq!(program.statements.len(), 1);
+
+        let Statement::ReturnStatement(ReturnStatement { expression: Expression::Function(function), .. }) = &program.statements[0] else {
+            panic!("expected a function literal, got {:?}", program.statements[0]);
+        };
+
+        let names: Vec<&str> = function.parameters.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["x", "y"]);
+        assert_eq!(function.body.statements.len(), 1);
+        assert_eq!((function.range.start, function.range.end), (7, 33));
+    }
+
+    #[test]
+    fn test_function_parameters() {
+        let tests = vec![
+            ("return fn() {};", 0),
+            ("return fn(x) {};", 1),
+            ("return fn(x, y, z) {};", 3),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(Lexer::new(input));
+            let program = parser.parse_program().unwrap();
+
+            let Statement::ReturnStatement(ReturnStatement { expression: Expression::Function(function), .. }) = &program.statements[0] else {
+                panic!("expected a function literal for {}", input);
+            };
+            assert_eq!(function.parameters.len(), expected);
+        }
+    }
+
+    #[test]
+    fn test_call_expression() {
+        let tests = vec![
+            ("return add(1, 2 * 3, 4 + 5);", 3),
+            ("return add();", 0),
+            ("return fn(x) { return x; }(5);", 1),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(Lexer::new(input));
+            let program = parser.parse_program().unwrap();
+            let Statement::ReturnStatement(ReturnStatement { expression: Expression::Call(call), .. }) = &program.statements[0] else {
+                panic!("expected a call expression for {}", input);
+            };
+            assert_eq!(call.arguments.len(), expected);
+            assert_eq!(call.range.end, input.len() - 1);
+        }
+    }
+
+    #[test]
+    fn test_if_expression() {
+        let input = "return if (x < y) { return x; };";
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program().unwrap();
+        let Statement::ReturnStatement(ReturnStatement { expression: Expression::If(if_expression), .. }) = &program.statements[0] else {
+            panic!("expected an if expression, got {:?}", program.statements[0]);
+        };
+
+        assert!(matches!(*if_expression.condition, Expression::Infix(_)));
+        assert_eq!(if_expression.consequence.statements.len(), 1);
+        assert!(if_expression.alternative.is_none());
+        assert_eq!((if_expression.range.start, if_expression.range.end), (7, 31));
+    }
+
+    #[test]
+    fn test_if_else_expression() {
+        let input = "return if (x < y) { return x; } else if (x > y) { return y; } else { return 0; };";
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program().unwrap();
+        let Statement::ReturnStatement(ReturnStatement { expression: Expression::If(if_expression), .. }) = &program.statements[0] else {
+            panic!("expected an if expression, got {:?}", program.statements[0]);
+        };
+        assert_eq!(if_expression.range.end, input.len() - 1);
+
+        let alternative = if_expression.alternative.as_ref().unwrap();
+        let Statement::Expression(Expression::If(nested)) = &alternative.statements[0] else {
+            panic!("expected a nested if expression, got {:?}", alternative.statements[0]);
+        };
+        assert_eq!((nested.range.start, nested.range.end), (37, input.len() - 1));
+        assert_eq!(nested.alternative.as_ref().unwrap().statements.len(), 1);
+    }
+
+    fn parse_returned_expression(input: &str) -> (Expression, TokenRange) {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program().unwrap();
+        match &program.statements[0] {
+            Statement::ReturnStatement(statement) => (statement.expression.clone(), statement.range),
+            statement => panic!("expected a return statement, got {:?}", statement),
+        }
+    }
+
+    #[test]
+    fn test_boolean_literals() {
+        for (input, expected) in [("return true;", true), ("return false;", false)] {
+            let (expression, _) = parse_returned_expression(input);
+            let Expression::Literal(Literal::Boolean(boolean)) = expression else {
+                panic!("expected a boolean literal, got {:?}", expression);
+            };
+            assert_eq!(boolean.value, expected);
+        }
+    }
+
+    #[test]
+    fn test_prefix_expressions() {
+        let tests = vec![
+            ("return !true;", TokenEnum::BANG, (7, 12)),
+            ("return -5;", TokenEnum::MINUS, (7, 9)),
+            ("return -(a + b);", TokenEnum::MINUS, (7, 15)),
+        ];
+
+        for (input, operator, (start, end)) in tests {
+            let (expression, _) = parse_returned_expression(input);
+            let Expression::Prefix(prefix) = expression else {
+                panic!("expected a prefix expression, got {:?}", expression);
+            };
+            assert_eq!(prefix.token.token_type, operator);
+            assert_eq!((prefix.range.start, prefix.range.end), (start, end));
+        }
+    }
+
+    #[test]
+    fn test_grouped_expression() {
+        let (expression, _) = parse_returned_expression("return (1 + 2) * 3;");
+        let Expression::Infix(infix) = expression else {
+            panic!("expected an infix expression, got {:?}", expression);
+        };
+
+        assert_eq!(infix.token.token_type, TokenEnum::ASTERISK);
+        assert!(matches!(*infix.left, Expression::Infix(_)));
+        assert_eq!((infix.range.start, infix.range.end), (7, 18));
+    }
+
+    #[test]
+    fn test_expression_statements() {
+        let lexer = Lexer::new("
+            foobar;
+            5;
+            1 + 2 * 3;
+            add(1, 2)
+        ");
+
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().unwrap();
+        assert_eq!(program.statements.len(), 4);
+        assert!(program.statements.iter().all(|s| matches!(s, Statement::Expression(_))));
+    }
+
+    #[test]
+    fn test_operator_precedence() {
+        let tests = vec![
+            ("-a * b", "((-a) * b)"),
+            ("!-a", "(!(-a))"),
+            ("a + b + c", "((a + b) + c)"),
+            ("a + b - c", "((a + b) - c)"),
+            ("a * b * c", "((a * b) * c)"),
+            ("a * b / c", "((a * b) / c)"),
+            ("a + b / c", "(a + (b / c))"),
+            ("a + b * c + d / e - f", "(((a + (b * c)) + (d / e)) - f)"),
+            ("3 + 4; -5 * 5", "(3 + 4)((-5) * 5)"),
+            ("5 > 4 == 3 < 4", "((5 > 4) == (3 < 4))"),
+            ("5 < 4 != 3 > 4", "((5 < 4) != (3 > 4))"),
+            ("3 + 4 * 5 == 3 * 1 + 4 * 5", "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))"),
+            ("true", "true"),
+            ("false", "false"),
+            ("3 > 5 == false", "((3 > 5) == false)"),
+            ("3 < 5 == true", "((3 < 5) == true)"),
+            ("1 + (2 + 3) + 4", "((1 + (2 + 3)) + 4)"),
+            ("(5 + 5) * 2", "((5 + 5) * 2)"),
+            ("2 / (5 + 5)", "(2 / (5 + 5))"),
+            ("-(5 + 5)", "(-(5 + 5))"),
+            ("!(true == true)", "(!(true == true))"),
+            ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
+            ("add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))", "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))"),
+            ("add(a + b + c * d / f + g)", "add((((a + b) + ((c * d) / f)) + g))"),
+            ("a * [1, 2, 3, 4][b * c] * d", "((a * ([1, 2, 3, 4][(b * c)])) * d)"),
+            ("add(a * b[2], b[1], 2 * [1, 2][1])", "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))"),
+            ("fns[0](1)", "(fns[0])(1)"),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(Lexer::new(input));
+            let program = parser.parse_program().unwrap_or_else(|errors| panic!("{}: {:?}", input, errors));
+
+            assert_eq!(program.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_infix_ranges() {
+        let input = "1 + 2 * 3;";
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program().unwrap();
+
+        let Statement::Expression(Expression::Infix(infix)) = &program.statements[0] else {
+            panic!("expected an infix expression, got {:?}", program.statements[0]);
+        };
+        assert_eq!((infix.range.start, infix.range.end), (0, 9));
+
+        let Expression::Infix(right) = &*infix.right else {
+            panic!("expected an infix expression, got {:?}", infix.right);
+        };
+        assert_eq!((right.range.start, right.range.end), (4, 9));
+    }
+
+    #[test]
+    fn test_prefix_parse_errors() {
+        let errors = Parser::new(Lexer::new("1 + ;")).parse_program().unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![ParseError {
+                kind: ParseErrorKind::ExpectedExpression,
+                range: TokenRange { start: 4, end: 5 },
+                found: TokenEnum::SEMICOLON,
+                expected: EXPRESSION_START.to_vec(),
+            }]
+        );
+        assert_eq!(errors[0].to_string(), "no prefix parse function for \";\" found.");
+        assert_eq!(errors[0].kind.code(), "E0002");
+    }
+
+    #[test]
+    fn test_let_statement_values() {
+        let tests = vec![
+            ("let x = 5;", "x", "5", (0, 10)),
+            ("let y = true;", "y", "true", (0, 13)),
+            ("let foobar = y + 1;", "foobar", "(y + 1)", (0, 19)),
+            ("let z = fn(a) { a }", "z", "fn(a) { a }", (0, 19)),
+        ];
+
+        for (input, name, value, (start, end)) in tests {
+            let mut parser = Parser::new(Lexer::new(input));
+            let program = parser.parse_program().unwrap_or_else(|errors| panic!("{}: {:?}", input, errors));
+
+            let Statement::LetStatement(statement) = &program.statements[0] else {
+                panic!("expected a let statement, got {:?}", program.statements[0]);
+            };
+            assert_eq!(statement.identifier.name, name);
+            assert_eq!(statement.expression.to_string(), value);
+            assert_eq!((statement.range.start, statement.range.end), (start, end));
+        }
+    }
+
+    #[test]
+    fn test_string_literal() {
+        let input = r#""hello\tworld" + "!""#;
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program().unwrap();
+        let Statement::Expression(Expression::Infix(infix)) = &program.statements[0] else {
+            panic!("expected an infix expression, got {:?}", program.statements[0]);
+        };
+        let Expression::Literal(Literal::String(string)) = &*infix.left else {
+            panic!("expected a string literal, got {:?}", infix.left);
+        };
+
+        assert_eq!(string.value, "hello\tworld");
+        assert_eq!((string.range.start, string.range.end), (0, 14));
+        assert_eq!(program.to_string(), r#"("hello\tworld" + "!")"#);
+    }
+
+    #[test]
+    fn test_array_literal() {
+        let (expression, _) = parse_returned_expression("return [1, 2 * 2, fn(x) { x }];");
+        let Expression::Literal(Literal::Array(array)) = expression else {
+            panic!("expected an array literal, got {:?}", expression);
+        };
+
+        assert_eq!(array.elements.len(), 3);
+        assert_eq!(array.elements[1].to_string(), "(2 * 2)");
+        assert_eq!((array.range.start, array.range.end), (7, 30));
+
+        let (expression, _) = parse_returned_expression("return [];");
+        assert_eq!(expression.to_string(), "[]");
+    }
+
+    #[test]
+    fn test_index_expression() {
+        let (expression, _) = parse_returned_expression("return myArray[1 + 1];");
+        let Expression::Index(index) = expression else {
+            panic!("expected an index expression, got {:?}", expression);
+        };
+
+        assert_eq!(index.left.to_string(), "myArray");
+        assert_eq!(index.index.to_string(), "(1 + 1)");
+        assert_eq!((index.range.start, index.range.end), (7, 21));
+    }
+
+    #[test]
+    fn test_hash_literal() {
+        let tests = vec![
+            (r#"return {"one": 1, "two": 2, "three": 3};"#, r#"{"one": 1, "two": 2, "three": 3}"#),
+            ("return {};", "{}"),
+            ("return {1: true, true: 0 + 1, x: 10 - 8};", "{1: true, true: (0 + 1), x: (10 - 8)}"),
+            (r#"return {"a": {"b": [1]}}["a"];"#, r#"({"a": {"b": [1]}}["a"])"#),
+        ];
+
+        for (input, expected) in tests {
+            let (expression, _) = parse_returned_expression(input);
+            assert_eq!(expression.to_string(), expected);
+        }
+
+        let (expression, _) = parse_returned_expression(r#"return {"one": 1};"#);
+        let Expression::Hash(hash) = expression else {
+            panic!("expected a hash literal, got {:?}", expression);
+        };
+        assert_eq!(hash.pairs.len(), 1);
+        assert_eq!((hash.range.start, hash.range.end), (7, 17));
+    }
+
+    #[test]
+    fn test_hash_literal_errors() {
+        let tests = vec![
+            (r#"{"one" 1}"#, vec![TokenEnum::COLON]),
+            (r#"{"one": 1 "two": 2}"#, vec![TokenEnum::COMMA, TokenEnum::RBRACE]),
+        ];
+
+        for (input, expected) in tests {
+            let errors = Parser::new(Lexer::new(input)).parse_program().unwrap_err();
+            assert_eq!(errors[0].kind, ParseErrorKind::UnexpectedToken, "{}", input);
+            assert_eq!(errors[0].expected, expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_error_details() {
+        let tests = vec![
+            (
+                "add(1 2)",
+                ParseErrorKind::UnexpectedToken,
+                (6, 7),
+                TokenEnum::INT(2),
+                vec![TokenEnum::COMMA, TokenEnum::RPAREN],
+                "expected next token to be \",\" or \")\", got \"2\" instead.",
+            ),
+            (
+                "fn(x, 1) { x }",
+                ParseErrorKind::ExpectedParameter,
+                (6, 7),
+                TokenEnum::INT(1),
+                vec![TokenEnum::IDENT { name: String::new() }],
+                "expected parameter to be IDENT, got \"1\" instead.",
+            ),
+            (
+                "let = 1",
+                ParseErrorKind::UnexpectedToken,
+                (4, 5),
+                TokenEnum::ASSIGN,
+                vec![TokenEnum::IDENT { name: String::new() }],
+                "expected next token to be IDENT, got \"=\" instead.",
+            ),
+            (
+                "if (x) { x",
+                ParseErrorKind::UnexpectedToken,
+                (10, 10),
+                TokenEnum::EOF,
+                vec![TokenEnum::RBRACE],
+                "expected next token to be \"}\", got \"EOF\" instead.",
+            ),
+            (
+                "let s = \"abc",
+                ParseErrorKind::IllegalToken,
+                (8, 12),
+                TokenEnum::ILLEGAL(Illegal::UnterminatedString),
+                EXPRESSION_START.to_vec(),
+                "unterminated string literal.",
+            ),
+            (
+                "\"a\\qb\" + 1",
+                ParseErrorKind::IllegalToken,
+                (0, 6),
+                TokenEnum::ILLEGAL(Illegal::InvalidEscape),
+                EXPRESSION_START.to_vec(),
+                "invalid escape sequence in string literal.",
+            ),
+            (
+                "let x 99999999999999999999",
+                ParseErrorKind::IllegalToken,
+                (6, 26),
+                TokenEnum::ILLEGAL(Illegal::IntegerOutOfRange),
+                vec![TokenEnum::ASSIGN],
+                "integer literal out of range.",
+            ),
+            (
+                "1 + @",
+                ParseErrorKind::IllegalToken,
+                (4, 5),
+                TokenEnum::ILLEGAL(Illegal::Character('@')),
+                EXPRESSION_START.to_vec(),
+                "illegal character '@'.",
+            ),
+        ];
+
+        for (input, kind, (start, end), found, expected, message) in tests {
+            let errors = Parser::new(Lexer::new(input)).parse_program().unwrap_err();
+
+            assert_eq!(errors.len(), 1, "{}: {:?}", input, errors);
+            assert_eq!(errors[0].kind, kind, "{}", input);
+            assert_eq!(errors[0].range, TokenRange { start, end }, "{}", input);
+            assert_eq!(errors[0].found, found, "{}", input);
+            assert_eq!(errors[0].expected, expected, "{}", input);
+            assert_eq!(errors[0].to_string(), message, "{}", input);
+        }
+    }
 }
@@ -8,20 +8,47 @@ pub struct Token {
     pub range: TokenRange,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct TokenRange {
     pub start: usize,
     pub end: usize,
 }
 
+impl TokenRange {
+    // the 1-based line and column where the range starts in input, with columns counted in chars.
+    // a range that doesn't fit input, like one read from a bytecode file, is clamped to it
+    pub fn line_col(&self, input: &str) -> (usize, usize) {
+        let mut start = self.start.min(input.len());
+        while !input.is_char_boundary(start) {
+            start -= 1;
+        }
+
+        let before = &input[..start];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+
+        (line, before[line_start..].chars().count() + 1)
+    }
+}
+
+// why a piece of input couldn't be lexed as any other token
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Illegal {
+    Character(char),
+    UnterminatedString,
+    InvalidEscape,
+    IntegerOutOfRange,
+}
+
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub enum TokenEnum {
-    ILLEGAL,
+    ILLEGAL(Illegal),
     EOF,
 
     // identifiers + literals
     IDENT { name: String }, // add, foobar, x, y, ...
-    INT(u32), // 123456
+    INT(i64), // 123456
+    STRING(String), // "foo bar"
 
     // operators
     ASSIGN,
@@ -40,11 +67,14 @@ pub enum TokenEnum {
     // delimeters
     COMMA,
     SEMICOLON,
+    COLON,
 
     LPAREN,
     RPAREN,
     LBRACE,
     RBRACE,
+    LBRACKET,
+    RBRACKET,
 
     // keywords
     FUNCTION,
@@ -59,10 +89,11 @@ pub enum TokenEnum {
 impl fmt::Display for TokenEnum {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            TokenEnum::ILLEGAL => write!(f, "ILLEGAL"),
+            TokenEnum::ILLEGAL(_) => write!(f, "ILLEGAL"),
             TokenEnum::EOF => write!(f, "EOF"),
             TokenEnum::IDENT { name } => write!(f, "{}", name),
             TokenEnum::INT(int) => write!(f, "{}", int),
+            TokenEnum::STRING(string) => write!(f, "{:?}", string),
             TokenEnum::ASSIGN => write!(f, "="),
             TokenEnum::PLUS => write!(f, "+"),
             TokenEnum::MINUS => write!(f, "-"),
@@ -75,10 +106,13 @@ impl fmt::Display for TokenEnum {
             TokenEnum::NEQ => write!(f, "!="),
             TokenEnum::COMMA => write!(f, ","),
             TokenEnum::SEMICOLON => write!(f, ";"),
+            TokenEnum::COLON => write!(f, ":"),
             TokenEnum::LPAREN => write!(f, "("),
             TokenEnum::RPAREN => write!(f, ")"),
             TokenEnum::LBRACE => write!(f, "{{"),
             TokenEnum::RBRACE => write!(f, "}}"),
+            TokenEnum::LBRACKET => write!(f, "["),
+            TokenEnum::RBRACKET => write!(f, "]"),
             TokenEnum::FUNCTION => write!(f, "fn"),
             TokenEnum::LET => write!(f, "let"),
             TokenEnum::TRUE => write!(f, "true"),
@@ -90,15 +124,20 @@ impl fmt::Display for TokenEnum {
     }
 }
 
+// the identifiers lookup_ident turns into keyword tokens, and the tokens they become
+pub const KEYWORDS: &[(&str, TokenEnum)] = &[
+    ("fn", TokenEnum::FUNCTION),
+    ("let", TokenEnum::LET),
+    ("true", TokenEnum::TRUE),
+    ("false", TokenEnum::FALSE),
+    ("if", TokenEnum::IF),
+    ("else", TokenEnum::ELSE),
+    ("return", TokenEnum::RETURN),
+];
+
 pub fn lookup_ident(ident: &str) -> TokenType {
-    match ident {
-        "fn" => TokenEnum::FUNCTION,
-        "let" => TokenEnum::LET,
-        "true" => TokenEnum::TRUE,
-        "false" => TokenEnum::FALSE,
-        "if" => TokenEnum::IF,
-        "else" => TokenEnum::ELSE,
-        "return" => TokenEnum::RETURN,
-        _ => TokenEnum::IDENT { name: ident.to_string() },
+    match KEYWORDS.iter().find(|(keyword, _)| *keyword == ident) {
+        Some((_, token_type)) => token_type.clone(),
+        None => TokenEnum::IDENT { name: ident.to_string() },
     }
 }
@@ -0,0 +1,24 @@
+use std::rc::Rc;
+
+use crate::object::object::Closure;
+
+// one function activation; the function's locals live on the VM stack starting at base_pointer
+pub struct Frame {
+    pub closure: Rc<Closure>,
+    pub ip: usize,
+    pub base_pointer: usize,
+}
+
+impl Frame {
+    pub fn new(closure: Rc<Closure>, base_pointer: usize) -> Self {
+        Frame {
+            closure,
+            ip: 0,
+            base_pointer,
+        }
+    }
+
+    pub fn instructions(&self) -> &[u8] {
+        &self.closure.function.instructions
+    }
+}
@@ -0,0 +1,508 @@
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::builtins::builtins::BUILTINS;
+use crate::code::code::{read_u16, Opcode};
+use crate::compiler::compiler::Bytecode;
+use crate::evaluator::evaluator::{eval_index_expression, eval_infix_expression, eval_prefix_expression, is_truthy};
+use crate::object::object::{Closure, CompiledFunction, Object};
+use crate::token::token::TokenEnum;
+
+use super::frame::Frame;
+
+pub const STACK_SIZE: usize = 2048;
+
+pub type VmError = String;
+
+// operators share their implementation with the evaluator so both engines agree on results and
+// error messages
+pub struct VM {
+    constants: Vec<Object>,
+    stack: Vec<Object>,
+    sp: usize,
+    globals: Vec<Object>,
+    frames: Vec<Frame>,
+    last_popped: Object,
+}
+
+impl VM {
+    pub fn new(bytecode: Bytecode) -> Self {
+        Self::new_with_globals(bytecode, Vec::new())
+    }
+
+    // the main program runs in a frame of its own, as a closure without parameters
+    pub fn new_with_globals(bytecode: Bytecode, globals: Vec<Object>) -> Self {
+        let main = CompiledFunction {
+            instructions: bytecode.instructions,
+            num_locals: 0,
+            num_parameters: 0,
+            positions: bytecode.positions,
+        };
+        let main = Closure {
+            function: Rc::new(main),
+            free: Vec::new(),
+        };
+
+        VM {
+            constants: bytecode.constants,
+            stack: vec![Object::Null; STACK_SIZE],
+            sp: 0,
+            globals,
+            frames: vec![Frame::new(Rc::new(main), 0)],
+            last_popped: Object::Null,
+        }
+    }
+
+    pub fn globals(&self) -> &[Object] {
+        &self.globals
+    }
+
+    // runs to completion and returns the program's value: the operand of a top-level return, or
+    // else the last value popped off the stack
+    pub fn run(&mut self) -> Result<Object, VmError> {
+        while self.frame().ip < self.frame().instructions().len() {
+            let ip = self.frame().ip;
+            let byte = self.frame().instructions()[ip];
+            let op = Opcode::from_byte(byte).ok_or_else(|| format!("unknown opcode {} at {}", byte, ip))?;
+            self.frame_mut().ip += 1;
+
+            match op {
+                Opcode::Constant => {
+                    let index = self.read_u16();
+                    self.push(self.constants[index].clone())?;
+                },
+                Opcode::Pop => {
+                    self.last_popped = self.pop();
+                },
+                Opcode::True => self.push(Object::Boolean(true))?,
+                Opcode::False => self.push(Object::Boolean(false))?,
+                Opcode::Null => self.push(Object::Null)?,
+                Opcode::Add
+                | Opcode::Sub
+                | Opcode::Mul
+                | Opcode::Div
+                | Opcode::Equal
+                | Opcode::NotEqual
+                | Opcode::GreaterThan
+                | Opcode::LessThan => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let result = eval_infix_expression(&infix_operator(op), left, right);
+                    self.push_result(result)?;
+                },
+                Opcode::Minus | Opcode::Bang => {
+                    let operator = if op == Opcode::Minus { TokenEnum::MINUS } else { TokenEnum::BANG };
+                    let right = self.pop();
+                    let result = eval_prefix_expression(&operator, right);
+                    self.push_result(result)?;
+                },
+                Opcode::Jump => {
+                    let target = self.read_u16();
+                    self.frame_mut().ip = target;
+                },
+                Opcode::JumpNotTruthy => {
+                    let target = self.read_u16();
+
+                    let condition = self.pop();
+                    if !is_truthy(&condition) {
+                        self.frame_mut().ip = target;
+                    }
+                },
+                Opcode::SetGlobal => {
+                    let index = self.read_u16();
+
+                    if index >= self.globals.len() {
+                        self.globals.resize(index + 1, Object::Null);
+                    }
+                    self.globals[index] = self.pop();
+                },
+                Opcode::GetGlobal => {
+                    let index = self.read_u16();
+                    self.push(self.globals.get(index).cloned().unwrap_or(Object::Null))?;
+                },
+                Opcode::SetLocal => {
+                    let index = self.read_u8();
+                    let slot = self.frame().base_pointer + index;
+                    self.stack[slot] = self.pop();
+                },
+                Opcode::GetLocal => {
+                    let index = self.read_u8();
+                    let slot = self.frame().base_pointer + index;
+                    self.push(self.stack[slot].clone())?;
+                },
+                Opcode::GetBuiltin => {
+                    let index = self.read_u8();
+                    self.push(Object::Builtin(BUILTINS[index]))?;
+                },
+                Opcode::GetFree => {
+                    let index = self.read_u8();
+                    self.push(self.frame().closure.free[index].clone())?;
+                },
+                Opcode::CurrentClosure => {
+                    self.push(Object::Closure(Rc::clone(&self.frame().closure)))?;
+                },
+                Opcode::Array => {
+                    let count = self.read_u16();
+
+                    let elements = self.stack[self.sp - count..self.sp].to_vec();
+                    self.sp -= count;
+                    self.push(Object::Array(elements))?;
+                },
+                Opcode::Hash => {
+                    let count = self.read_u16();
+
+                    let hash = self.build_hash(self.sp - count, self.sp)?;
+                    self.sp -= count;
+                    self.push(hash)?;
+                },
+                Opcode::Index => {
+                    let index = self.pop();
+                    let left = self.pop();
+                    self.push_result(eval_index_expression(left, index))?;
+                },
+                Opcode::Closure => {
+                    let index = self.read_u16();
+                    let num_free = self.read_u8();
+                    self.push_closure(index, num_free)?;
+                },
+                Opcode::Call => {
+                    let num_args = self.read_u8();
+                    self.call(num_args)?;
+                },
+                Opcode::ReturnValue | Opcode::Return => {
+                    let value = if op == Opcode::ReturnValue { self.pop() } else { Object::Null };
+                    if self.frames.len() == 1 {
+                        return Ok(value);
+                    }
+
+                    // drop the callee's locals and the callee itself
+                    let frame = self.frames.pop().unwrap();
+                    self.sp = frame.base_pointer - 1;
+                    self.push(value)?;
+                },
+            }
+        }
+
+        Ok(self.last_popped.clone())
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().unwrap()
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn read_u16(&mut self) -> usize {
+        let frame = self.frame_mut();
+        let operand = read_u16(&frame.closure.function.instructions, frame.ip) as usize;
+        frame.ip += 2;
+        operand
+    }
+
+    fn read_u8(&mut self) -> usize {
+        let frame = self.frame_mut();
+        let operand = frame.closure.function.instructions[frame.ip] as usize;
+        frame.ip += 1;
+        operand
+    }
+
+    fn push_closure(&mut self, index: usize, num_free: usize) -> Result<(), VmError> {
+        let function = match &self.constants[index] {
+            Object::CompiledFunction(function) => Rc::clone(function),
+            other => return Err(format!("not a function: {}", other.type_name())),
+        };
+
+        let free = self.stack[self.sp - num_free..self.sp].to_vec();
+        self.sp -= num_free;
+        self.push(Object::Closure(Rc::new(Closure { function, free })))
+    }
+
+    // the callee sits below its arguments; a closure's arguments become its first locals
+    fn call(&mut self, num_args: usize) -> Result<(), VmError> {
+        let callee = self.stack[self.sp - 1 - num_args].clone();
+
+        match callee {
+            Object::Closure(closure) => {
+                let function = &closure.function;
+                if function.num_parameters != num_args {
+                    return Err(format!(
+                        "wrong number of arguments: want={}, got={}",
+                        function.num_parameters, num_args
+                    ));
+                }
+
+                let base_pointer = self.sp - num_args;
+                let sp = base_pointer + function.num_locals;
+                if sp > STACK_SIZE {
+                    return Err("stack overflow".to_string());
+                }
+
+                self.frames.push(Frame::new(Rc::clone(&closure), base_pointer));
+                self.sp = sp;
+                Ok(())
+            },
+            Object::Builtin(builtin) => self.call_native(num_args, builtin.function),
+            Object::HostFunction(host) => self.call_native(num_args, &host.function),
+            other => Err(format!("not a function: {}", other.type_name())),
+        }
+    }
+
+    fn call_native(&mut self, num_args: usize, function: impl Fn(Vec<Object>) -> Object) -> Result<(), VmError> {
+        let arguments = self.stack[self.sp - num_args..self.sp].to_vec();
+        self.sp -= num_args + 1;
+        self.push_result(function(arguments))
+    }
+
+    fn build_hash(&self, start: usize, end: usize) -> Result<Object, VmError> {
+        let mut pairs = BTreeMap::new();
+
+        for pair in self.stack[start..end].chunks(2) {
+            let (key, value) = (&pair[0], &pair[1]);
+            match key.hash_key() {
+                Some(hash_key) => pairs.insert(hash_key, value.clone()),
+                None => return Err(format!("unusable as hash key: {}", key.type_name())),
+            };
+        }
+
+        Ok(Object::Hash(pairs))
+    }
+
+    // error objects abort execution, the same way they stop the evaluator
+    fn push_result(&mut self, result: Object) -> Result<(), VmError> {
+        match result {
+            Object::Error(message) => Err(message),
+            result => self.push(result),
+        }
+    }
+
+    fn push(&mut self, object: Object) -> Result<(), VmError> {
+        if self.sp >= STACK_SIZE {
+            return Err("stack overflow".to_string());
+        }
+
+        self.stack[self.sp] = object;
+        self.sp += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Object {
+        self.sp -= 1;
+        std::mem::replace(&mut self.stack[self.sp], Object::Null)
+    }
+}
+
+fn infix_operator(op: Opcode) -> TokenEnum {
+    match op {
+        Opcode::Add => TokenEnum::PLUS,
+        Opcode::Sub => TokenEnum::MINUS,
+        Opcode::Mul => TokenEnum::ASTERISK,
+        Opcode::Div => TokenEnum::SLASH,
+        Opcode::Equal => TokenEnum::EQ,
+        Opcode::NotEqual => TokenEnum::NEQ,
+        Opcode::GreaterThan => TokenEnum::GT,
+        Opcode::LessThan => TokenEnum::LT,
+        _ => unreachable!("{:?} is not an infix operator", op),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::compiler::compiler::compile_source;
+    use crate::evaluator::evaluator::eval_program;
+    use crate::lexer::lexer::Lexer;
+    use crate::mbc::mbc;
+    use crate::object::environment::Environment;
+    use crate::parser::parser::Parser;
+
+    // compile and runtime errors are turned into error objects so results line up with the evaluator
+    fn run(input: &str) -> Object {
+        run_with(input, true)
+    }
+
+    fn run_with(input: &str, optimize: bool) -> Object {
+        let bytecode = match compile_source(input, optimize) {
+            Ok(bytecode) => bytecode,
+            Err(error) => return Object::Error(error),
+        };
+
+        // every program the compiler produces has to pass the checks made when loading a .mbc file
+        let bytecode = mbc::decode(&mbc::encode(&bytecode, true).unwrap())
+            .unwrap_or_else(|error| panic!("{}: {}", input, error));
+
+        let mut vm = VM::new(bytecode);
+        vm.run().unwrap_or_else(Object::Error)
+    }
+
+    fn eval(input: &str) -> Object {
+        let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+        eval_program(&program, &Rc::new(RefCell::new(Environment::new())))
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        let tests = vec![
+            ("1", Object::Integer(1)),
+            ("1 + 2", Object::Integer(3)),
+            ("4 / 2 * 3 - 1", Object::Integer(5)),
+            ("5 * (2 + 10)", Object::Integer(60)),
+            ("-50 + 100 + -50", Object::Integer(0)),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", Object::Integer(50)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_conditionals_and_globals() {
+        let tests = vec![
+            ("if (true) { 10 }", Object::Integer(10)),
+            ("if (1 > 2) { 10 }", Object::Null),
+            ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
+            ("if ((if (false) { 10 })) { 10 } else { 20 }", Object::Integer(20)),
+            ("let one = 1; let two = one + one; one + two", Object::Integer(3)),
+            ("let x = 1;", Object::Null),
+            ("if (true) { let y = 2; }", Object::Null),
+            ("return 5; 10", Object::Integer(5)),
+            ("if (true) { return 1; } 2", Object::Integer(1)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let tests = vec![
+            ("1 + true", "type mismatch: INTEGER + BOOLEAN"),
+            ("-true", "unknown operator: -BOOLEAN"),
+            ("1 / 0", "division by zero"),
+            ("{[1]: 2}", "unusable as hash key: ARRAY"),
+            ("1[0]", "index operator not supported: INTEGER"),
+            ("len(1)", "argument to `len` not supported, got INTEGER"),
+            ("len(1, 2)", "wrong number of arguments. got=2, want=1"),
+            ("1(2)", "not a function: INTEGER"),
+            ("fn(a) { a }()", "wrong number of arguments: want=1, got=0"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Object::Error(expected.to_string()), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_functions() {
+        let tests = vec![
+            ("let f = fn() { 5 + 10 }; f()", Object::Integer(15)),
+            ("let f = fn() { return 1; 2 }; f()", Object::Integer(1)),
+            ("let f = fn() { }; f()", Object::Null),
+            ("let f = fn(a, b) { let c = a + b; c * 2 }; f(1, 2) + f(3, 4)", Object::Integer(20)),
+            ("let g = 10; let f = fn(a) { let g = a; g }; f(1) + g", Object::Integer(11)),
+            ("let first = fn() { 1 }; let second = fn() { first() + 1 }; second()", Object::Integer(2)),
+            ("let apply = fn(f, x) { f(x) }; apply(len, [1, 2])", Object::Integer(2)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_closures() {
+        let tests = vec![
+            ("let adder = fn(a) { fn(b) { a + b } }; adder(2)(3)", Object::Integer(5)),
+            ("let f = fn(a) { fn(b) { fn(c) { a + b + c } } }; f(1)(2)(3)", Object::Integer(6)),
+            (
+                "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15)",
+                Object::Integer(610),
+            ),
+            (
+                "let wrap = fn() { let count = fn(n) { if (n == 0) { 0 } else { 1 + count(n - 1) } }; count(5) }; wrap()",
+                Object::Integer(5),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let input = format!("[{}]", vec!["1"; STACK_SIZE + 1].join(", "));
+
+        assert_eq!(run(&input), Object::Error("stack overflow".to_string()));
+        assert_eq!(run("let f = fn() { f() }; f()"), Object::Error("stack overflow".to_string()));
+    }
+
+    #[test]
+    fn test_matches_evaluator() {
+        let inputs = vec![
+            "1 + 2 * 3 - 4 / 2",
+            "!true == false",
+            "!!5",
+            "!(if (false) { 5; })",
+            "1 < 2 != 2 > 1",
+            r#""mon" + "key""#,
+            r#""a" == "a""#,
+            r#""a" - "b""#,
+            "[1, 2 * 2, 3 + 3][2]",
+            "[1, 2, 3][3]",
+            "[1, 2, 3][-1]",
+            r#"{"one": 1, "two": 2}["two"]"#,
+            r#"{"one": 1}["three"]"#,
+            "{1: 1, 2: 2}",
+            "let a = 5; let b = a * 2; if (b > a) { b } else { a }",
+            "let a = 1; let a = a + 1; a",
+            "if (false) { 1 }",
+            "if (1) { }",
+            "5; return 10 * 2; 9;",
+            "true + false",
+            "1 + true; 5",
+            r#"len("four") + len([1, 2])"#,
+            "first(rest(push([1, 2], 3)))",
+            "last([])",
+            "let len = 5; len",
+            "let max = fn(a, b) { if (a > b) { a } else { b } }; max(3, 7)",
+            "let f = fn(x) { return x; 99 }; f(4) * 2",
+            "let f = fn(x) { if (x > 1) { return 10; } 20 }; [f(1), f(2)]",
+            "let map = fn(arr, f) { let iter = fn(arr, acc) { if (len(arr) == 0) { acc } else { iter(rest(arr), push(acc, f(first(arr)))) } }; iter(arr, []) }; map([1, 2, 3], fn(x) { x * x })",
+            "let f = fn(n) { f(n + 1) }; f(0)",
+            "let f = fn() { g() }; let g = fn() { 1 }; f()",
+            "let a = len([1, 2]); let len = fn(x) { 0 }; a + len([1])",
+            "let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) }; fib(10)",
+            "let x = 1; let f = fn() { let x = x + 1; x }; [f(), x]",
+            "let f = fn() { 1 + true }; f(); 5",
+            "1 + fn() { 1 }",
+            "60 * 60 * 24",
+            "-(1 - 2) * 3 == 3",
+            "!(1 > 2) == !false",
+            "if (1 > 2) { 1 } else { 2 }",
+            "if (10 / 0) { 1 }",
+            "let a = 1; a; 2; len; a",
+            "1700000000000 + 1",
+            "1; 2; 3",
+            "let f = fn(x) { x; 1; if (true) { x } else { 0 } }; f(7)",
+            "let c = 0; if (c) { 1 } else { 2 }; 3",
+        ];
+
+        for input in inputs {
+            let expected = eval(input);
+            assert_eq!(run_with(input, true), expected, "{}", input);
+            assert_eq!(run_with(input, false), expected, "{}", input);
+        }
+
+        // the evaluator looks a captured local up when the closure runs, while the VM copies its
+        // value when the closure is created
+        let input = "let g = fn() { let x = 1; let h = fn() { x }; let x = 2; h() }; g()";
+        assert_eq!(eval(input), Object::Integer(2));
+        assert_eq!(run(input), Object::Integer(1));
+    }
+}
@@ -0,0 +1,169 @@
+use std::time::Instant;
+
+use monkey_lib::code::disassembler::disassemble;
+use monkey_lib::compiler::compiler::Compiler;
+use monkey_lib::lexer::lexer::Lexer;
+use monkey_lib::object::environment::{Env, Environment};
+use monkey_lib::token::token::TokenEnum;
+
+use super::{eval_input, parse};
+
+const HELP: &str = "\
+:tokens <input>    show the tokens the lexer produces for input
+:ast <input>       show how input parses
+:bytecode <input>  show the bytecode input compiles to
+:env               list the bindings in the session
+:load <file>       evaluate a file into the session
+:reset             remove every binding from the session
+:time <input>      evaluate input and report how long it took
+:help              show this list
+";
+
+pub fn is_command(input: &str) -> bool {
+    input.trim_start().starts_with(':')
+}
+
+// returns what to print; the input of a command is everything after its name
+pub fn run_command(input: &str, env: &Env) -> String {
+    let input = input.trim();
+    let (name, argument) = match input.split_once(char::is_whitespace) {
+        Some((name, argument)) => (name, argument.trim()),
+        None => (input, ""),
+    };
+
+    match name {
+        ":tokens" => tokens(argument),
+        ":ast" => ast(argument),
+        ":bytecode" => bytecode(argument, env),
+        ":env" => bindings(env),
+        ":load" => load(argument, env),
+        ":reset" => {
+            *env.borrow_mut() = Environment::new();
+            String::new()
+        },
+        ":time" => {
+            let start = Instant::now();
+            let output = eval_input(argument, env);
+            format!("{}took {:?}\n", output, start.elapsed())
+        },
+        ":help" => HELP.to_string(),
+        _ => format!("unknown command {}, try :help\n", name),
+    }
+}
+
+fn tokens(input: &str) -> String {
+    let mut lexer = Lexer::new(input);
+    let mut out = String::new();
+
+    loop {
+        let token = lexer.next_token();
+        if token.token_type == TokenEnum::EOF {
+            break;
+        }
+        out.push_str(&format!("{:?}\n", token));
+    }
+
+    out
+}
+
+fn ast(input: &str) -> String {
+    match parse(input) {
+        Ok(program) => program.statements.iter().map(|statement| format!("{}\n", statement)).collect(),
+        Err(errors) => errors,
+    }
+}
+
+// names bound in the session compile as globals, so input can refer to them. the session lives
+// in the evaluator, which has no global slots, so they are numbered just for the listing and
+// printed above it
+fn bytecode(input: &str, env: &Env) -> String {
+    let program = match parse(input) {
+        Ok(program) => program,
+        Err(errors) => return errors,
+    };
+
+    let names = env.borrow().names();
+    let mut compiler = Compiler::new_with_globals(&names);
+    if let Err(error) = compiler.compile(&program) {
+        return format!("compile error: {}\n", error);
+    }
+
+    let mut out = String::new();
+    if !names.is_empty() {
+        let slots: Vec<String> = names.iter().enumerate().map(|(index, name)| format!("{} {}", index, name)).collect();
+        out.push_str(&format!("session globals, numbered for this listing: {}\n", slots.join(", ")));
+    }
+    out.push_str(&disassemble(&compiler.bytecode()));
+    out
+}
+
+fn bindings(env: &Env) -> String {
+    let env = env.borrow();
+
+    env.names()
+        .iter()
+        .map(|name| format!("{} = {}\n", name, env.get(name).unwrap()))
+        .collect()
+}
+
+fn load(path: &str, env: &Env) -> String {
+    if path.is_empty() {
+        return "usage: :load <file>\n".to_string();
+    }
+
+    match std::fs::read_to_string(path) {
+        Ok(input) => eval_input(&input, env),
+        Err(error) => format!("{}: {}\n", path, error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    fn session() -> Env {
+        Rc::new(RefCell::new(Environment::new()))
+    }
+
+    #[test]
+    fn test_commands() {
+        let env = session();
+        eval_input("let x = 5; let add = fn(a, b) { a + b };", &env);
+
+        assert_eq!(
+            run_command(":tokens x;", &env),
+            "Token { token_type: IDENT { name: \"x\" }, range: TokenRange { start: 0, end: 1 } }\n\
+             Token { token_type: SEMICOLON, range: TokenRange { start: 1, end: 2 } }\n"
+        );
+        assert_eq!(run_command(":ast -a * b; let y = 1", &env), "((-a) * b)\nlet y = 1;\n");
+        assert_eq!(run_command(":ast let = 1", &env), "parse error[E0001]: expected next token to be IDENT, got \"=\" instead.\n    let = 1\n        ^\n");
+        let listing = run_command(":bytecode add(x, 1)", &env);
+        assert!(listing.starts_with("session globals, numbered for this listing: 0 add, 1 x\nmain:\n"));
+        assert!(listing.contains("OpGetGlobal 1"));
+        assert_eq!(run_command(":bytecode nope", &env), "compile error: identifier not found: nope\n");
+        assert_eq!(run_command(":env", &env), "add = fn(a, b) { (a + b) }\nx = 5\n");
+        assert!(run_command(":time add(x, 2)", &env).starts_with("7\ntook "));
+        assert_eq!(run_command(":frobnicate", &env), "unknown command :frobnicate, try :help\n");
+
+        assert_eq!(run_command(":reset", &env), "");
+        assert_eq!(run_command(":env", &env), "");
+        assert_eq!(eval_input("x", &env), "ERROR: identifier not found: x\n");
+    }
+
+    #[test]
+    fn test_load() {
+        let path = std::env::temp_dir().join(format!("monkey_repl_load_{}.monkey", std::process::id()));
+        std::fs::write(&path, "let double = fn(x) {\n  x * 2\n};\ndouble(21)\n").unwrap();
+
+        let env = session();
+        assert_eq!(run_command(&format!(":load {}", path.display()), &env), "42\n");
+        assert_eq!(eval_input("double(2)", &env), "4\n");
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(run_command(&format!(":load {}", path.display()), &env).starts_with(&path.display().to_string()));
+        assert_eq!(run_command(":load", &env), "usage: :load <file>\n");
+    }
+}
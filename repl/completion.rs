@@ -0,0 +1,101 @@
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+use monkey_lib::builtins::builtins::BUILTINS;
+use monkey_lib::object::environment::Env;
+use monkey_lib::token::token::KEYWORDS;
+
+// completes the identifier before the cursor from keywords, builtins and the session's bindings
+pub struct MonkeyHelper {
+    env: Env,
+}
+
+impl MonkeyHelper {
+    pub fn new(env: Env) -> Self {
+        MonkeyHelper { env }
+    }
+
+    fn candidates(&self, prefix: &str) -> Vec<String> {
+        let mut names: Vec<String> = KEYWORDS
+            .iter()
+            .map(|(keyword, _)| keyword)
+            .chain(BUILTINS.iter().map(|builtin| &builtin.name))
+            .map(|name| name.to_string())
+            .chain(self.env.borrow().names())
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        names.sort();
+        names.dedup();
+
+        names
+    }
+}
+
+impl Completer for MonkeyHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .take_while(|(_, ch)| ch.is_alphanumeric() || *ch == '_')
+            .last()
+            .map_or(pos, |(index, _)| index);
+
+        Ok((start, self.candidates(&line[start..pos])))
+    }
+}
+
+impl Hinter for MonkeyHelper {
+    type Hint = String;
+}
+
+impl Highlighter for MonkeyHelper {}
+
+impl Validator for MonkeyHelper {}
+
+impl Helper for MonkeyHelper {}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use rustyline::history::DefaultHistory;
+
+    use super::*;
+    use monkey_lib::object::environment::Environment;
+    use monkey_lib::object::object::Object;
+
+    #[test]
+    fn test_complete() {
+        let env = Rc::new(RefCell::new(Environment::new()));
+        env.borrow_mut().set("length", Object::Integer(1));
+        env.borrow_mut().set("rest_of", Object::Null);
+
+        let helper = MonkeyHelper::new(env);
+        let history = DefaultHistory::new();
+        let ctx = Context::new(&history);
+
+        let tests = vec![
+            ("le", 2, (0, vec!["len", "length", "let"])),
+            ("1 + re", 6, (4, vec!["rest", "rest_of", "return"])),
+            ("puts(fir", 8, (5, vec!["first"])),
+            ("tr x", 2, (0, vec!["true"])),
+            ("zzz", 3, (0, vec![])),
+        ];
+
+        for (line, pos, (start, expected)) in tests {
+            let (found_start, candidates) = helper.complete(line, pos, &ctx).unwrap();
+            assert_eq!(found_start, start, "{}", line);
+            assert_eq!(candidates, expected, "{}", line);
+        }
+
+        let (start, candidates) = helper.complete("f(x) + ", 7, &ctx).unwrap();
+        assert_eq!(start, 7);
+        assert_eq!(candidates.len(), KEYWORDS.len() + BUILTINS.len() + 2);
+    }
+}
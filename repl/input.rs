@@ -0,0 +1,83 @@
+use monkey_lib::lexer::lexer::Lexer;
+use monkey_lib::token::token::{Illegal, TokenEnum};
+
+// input is incomplete while a bracket is left open, a string is unterminated or the last token
+// is an operator still waiting for its right-hand side or an else waiting for its block; anything
+// else goes to the parser, which reports whatever is actually wrong with it
+pub fn is_incomplete(input: &str) -> bool {
+    let mut lexer = Lexer::new(input);
+    let mut depth: i64 = 0;
+    let mut last = TokenEnum::EOF;
+
+    loop {
+        let token = lexer.next_token();
+
+        match token.token_type {
+            TokenEnum::EOF => break,
+            TokenEnum::LBRACE | TokenEnum::LPAREN | TokenEnum::LBRACKET => depth += 1,
+            TokenEnum::RBRACE | TokenEnum::RPAREN | TokenEnum::RBRACKET => depth -= 1,
+            TokenEnum::ILLEGAL(Illegal::UnterminatedString) => return true,
+            _ => {},
+        }
+        last = token.token_type;
+    }
+
+    depth > 0
+        || matches!(
+            last,
+            TokenEnum::ASSIGN
+                | TokenEnum::PLUS
+                | TokenEnum::MINUS
+                | TokenEnum::BANG
+                | TokenEnum::ASTERISK
+                | TokenEnum::SLASH
+                | TokenEnum::LT
+                | TokenEnum::GT
+                | TokenEnum::EQ
+                | TokenEnum::NEQ
+                | TokenEnum::COMMA
+                | TokenEnum::COLON
+                | TokenEnum::ELSE
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_incomplete() {
+        let tests = vec![
+            ("", false),
+            ("let x = 5;", false),
+            ("let f = fn(x) {", true),
+            ("let f = fn(x) {\n  x + 1\n}", false),
+            ("[1, 2,", true),
+            ("{\"a\": ", true),
+            ("add(1, (2", true),
+            ("1 +", true),
+            ("1 -", true),
+            ("2 *", true),
+            ("4 /", true),
+            ("1 <", true),
+            ("1 >", true),
+            ("x ==", true),
+            ("x !=", true),
+            ("if (x) { 1 } else", true),
+            ("if (x) { 1 }", false),
+            ("let x =", true),
+            ("!", true),
+            ("x == y", false),
+            ("}", false),
+            ("let s = \"hello", true),
+            ("let s = \"hello\nworld\"", false),
+            ("\"escaped \\\"", true),
+            ("\"bad \\q escape\"", false),
+            ("\"{\"", false),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(is_incomplete(input), expected, "{:?}", input);
+        }
+    }
+}
@@ -1,32 +1,118 @@
-use std::io::{stdin, Write};
+mod commands;
+mod completion;
+mod input;
 
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use rustyline::error::ReadlineError;
+use rustyline::history::FileHistory;
+use rustyline::{CompletionType, Config, Editor};
+
+use monkey_lib::evaluator::evaluator::eval_program;
 use monkey_lib::lexer::lexer::Lexer;
-use monkey_lib::token::token::TokenEnum;
+use monkey_lib::object::environment::{Env, Environment};
+use monkey_lib::parser::ast::{Program, Statement};
+use monkey_lib::parser::parser::Parser;
+
+use commands::{is_command, run_command};
+use completion::MonkeyHelper;
+use input::is_incomplete;
 
 const PROMPT: &str = ">> ";
+const CONTINUATION_PROMPT: &str = ".. ";
+const HISTORY_FILE: &str = ".monkey_history";
 
+// lines are collected until they form a complete input or meta-command; an empty line evaluates
+// whatever has been typed so far, so a stray bracket can't trap the session in continuation mode,
+// and ctrl-c discards it
 pub fn main() {
-    
+    let env: Env = Rc::new(RefCell::new(Environment::new()));
+
+    let config = Config::builder()
+        .auto_add_history(false)
+        .completion_type(CompletionType::List)
+        .build();
+    let mut editor: Editor<MonkeyHelper, FileHistory> = Editor::with_config(config).expect("failed to set up the terminal");
+    editor.set_helper(Some(MonkeyHelper::new(Rc::clone(&env))));
+
+    let history = history_path();
+    if let Some(path) = &history {
+        // there is no history yet on the first run
+        let _ = editor.load_history(path);
+    }
+
+    let mut buffer = String::new();
     loop {
-        print!("{}", PROMPT);
-        std::io::stdout().flush().unwrap();
-
-        let mut buffer = String::new();
-        let mut lexer = match stdin().read_line(&mut buffer) {
-            Ok(_bytes) => {
-                let lex = Lexer::new(&buffer);
-                lex
-            },
-            Err(error) => panic!("Error: {}", error)
-        };
+        let prompt = if buffer.is_empty() { PROMPT } else { CONTINUATION_PROMPT };
+
+        match editor.readline(prompt) {
+            Ok(line) => {
+                let abandon = !buffer.is_empty() && line.trim().is_empty();
+                buffer.push_str(&line);
+                buffer.push('\n');
 
-        loop {
-            let token = lexer.next_token();
-            if token.token_type == TokenEnum::EOF {
-                break;
-            }
+                if abandon || !is_incomplete(&buffer) {
+                    if !buffer.trim().is_empty() {
+                        let _ = editor.add_history_entry(buffer.trim_end());
+                    }
+                    if is_command(&buffer) {
+                        print!("{}", run_command(&buffer, &env));
+                    } else {
+                        print!("{}", eval_input(&buffer, &env));
+                    }
+                    buffer.clear();
+                }
+            },
+            Err(ReadlineError::Interrupted) => buffer.clear(),
+            Err(ReadlineError::Eof) => break,
+            Err(error) => panic!("Error: {}", error),
+        }
+    }
 
-            println!("{:?}", token);
+    if let Some(path) = &history {
+        if let Err(error) = editor.save_history(path) {
+            eprintln!("could not save history to {}: {}", path.display(), error);
         }
     }
 }
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+// bindings made by one input stay in env for the next; returns what to print, which is nothing
+// for a let statement
+fn eval_input(input: &str, env: &Env) -> String {
+    let program = match parse(input) {
+        Ok(program) => program,
+        Err(errors) => return errors,
+    };
+
+    let result = eval_program(&program, env);
+    if matches!(program.statements.last(), None | Some(Statement::LetStatement(_))) {
+        return String::new();
+    }
+    format!("{}\n", result)
+}
+
+// parse errors come back ready to print, each shown under the line it was found on with a caret
+// at its column
+fn parse(input: &str) -> Result<Program, String> {
+    // errors at the end of input are then shown after its last token, not on an empty line
+    let input = input.trim_end();
+
+    Parser::new(Lexer::new(input)).parse_program().map_err(|errors| {
+        let mut out = String::new();
+        for error in errors {
+            let (line, column) = error.range.line_col(input);
+            let source = input.lines().nth(line - 1).unwrap_or("");
+
+            out.push_str(&format!("parse error[{}]: {}\n", error.kind.code(), error));
+            out.push_str(&format!("    {}\n", source));
+            out.push_str(&format!("    {}^\n", " ".repeat(column - 1)));
+        }
+        out
+    })
+}
@@ -1,3 +1,199 @@
+use std::cell::RefCell;
+use std::io::Read;
+use std::path::Path;
+use std::process::ExitCode;
+use std::rc::Rc;
 
-fn main() {
+use monkey_lib::code::disassembler::disassemble;
+use monkey_lib::compiler::compiler::{Bytecode, Compiler};
+use monkey_lib::evaluator::evaluator::eval_program;
+use monkey_lib::lexer::lexer::Lexer;
+use monkey_lib::mbc::mbc;
+use monkey_lib::object::environment::Environment;
+use monkey_lib::object::object::Object;
+use monkey_lib::parser::ast::Program;
+use monkey_lib::parser::parser::Parser;
+use monkey_lib::vm::vm::VM;
+
+const USAGE: &str = "usage:
+    monkey_exec [--engine=eval|vm] [--no-optimize] <script|program.mbc|->
+    monkey_exec compile <source> [-o <output>] [--strip] [--no-optimize]
+    monkey_exec disasm <source|program.mbc> [--no-optimize]";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Engine {
+    Eval,
+    Vm,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = match args.first().map(String::as_str) {
+        Some("compile") => compile(&args[1..]),
+        Some("disasm") => disasm(&args[1..]),
+        _ => execute(&args),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        },
+    }
+}
+
+// runs a script, or a compiled program on the vm, and prints its value unless it is null
+fn execute(args: &[String]) -> Result<(), String> {
+    let mut path = None;
+    let mut engine = None;
+    let mut optimize = true;
+
+    for arg in args {
+        match arg.as_str() {
+            "--engine=eval" => engine = Some(Engine::Eval),
+            "--engine=vm" => engine = Some(Engine::Vm),
+            "--no-optimize" => optimize = false,
+            _ if path.is_none() && (arg == "-" || !arg.starts_with('-')) => path = Some(arg.as_str()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let path = path.ok_or(USAGE)?;
+    let (name, bytes) = read_input(path)?;
+
+    let result = if bytes.starts_with(mbc::MAGIC) {
+        if engine == Some(Engine::Eval) {
+            return Err(format!("{}: compiled programs can only run on the vm engine", name));
+        }
+        if !optimize {
+            return Err(format!("{}: --no-optimize has no effect on compiled programs", name));
+        }
+        let bytecode = mbc::decode(&bytes).map_err(|error| format!("{}: {}", name, error))?;
+        VM::new(bytecode).run()
+    } else {
+        let input = String::from_utf8(bytes).map_err(|_| format!("{}: not valid utf-8", name))?;
+        let program = parse(&name, &input)?;
+
+        match engine.unwrap_or(Engine::Eval) {
+            Engine::Eval => match eval_program(&program, &Rc::new(RefCell::new(Environment::new()))) {
+                Object::Error(message) => Err(message),
+                result => Ok(result),
+            },
+            Engine::Vm => {
+                let bytecode = compile_program(&name, &input, &program, optimize)?;
+                VM::new(bytecode).run()
+            },
+        }
+    };
+
+    match result {
+        Ok(Object::Null) => Ok(()),
+        Ok(result) => {
+            println!("{}", result);
+            Ok(())
+        },
+        Err(message) => Err(format!("{}: runtime error: {}", name, message)),
+    }
+}
+
+// writes <source> compiled to <output>, by default the source path with an .mbc extension;
+// --strip leaves out the debug position table
+fn compile(args: &[String]) -> Result<(), String> {
+    let mut source = None;
+    let mut output = None;
+    let mut strip = false;
+    let mut optimize = true;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output = Some(args.next().ok_or(USAGE)?.clone()),
+            "--strip" => strip = true,
+            "--no-optimize" => optimize = false,
+            _ if source.is_none() => source = Some(arg.clone()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let source = source.ok_or(USAGE)?;
+    let output = output.unwrap_or_else(|| Path::new(&source).with_extension("mbc").display().to_string());
+
+    let input = std::fs::read_to_string(&source).map_err(|error| format!("{}: {}", source, error))?;
+    let program = parse(&source, &input)?;
+    let bytecode = compile_program(&source, &input, &program, optimize)?;
+
+    let bytes = mbc::encode(&bytecode, !strip)?;
+    std::fs::write(&output, bytes).map_err(|error| format!("{}: {}", output, error))
+}
+
+// accepts either a compiled program or Monkey source, which is compiled first
+fn disasm(args: &[String]) -> Result<(), String> {
+    let mut path = None;
+    let mut optimize = true;
+
+    for arg in args {
+        match arg.as_str() {
+            "--no-optimize" => optimize = false,
+            _ if path.is_none() && (arg == "-" || !arg.starts_with('-')) => path = Some(arg.as_str()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let path = path.ok_or(USAGE)?;
+
+    let (name, bytes) = read_input(path)?;
+    let bytecode = if bytes.starts_with(mbc::MAGIC) {
+        if !optimize {
+            return Err(format!("{}: --no-optimize has no effect on compiled programs", name));
+        }
+        mbc::decode(&bytes).map_err(|error| format!("{}: {}", name, error))?
+    } else {
+        let input = String::from_utf8(bytes).map_err(|_| format!("{}: not a Monkey source or bytecode file", name))?;
+        let program = parse(&name, &input)?;
+        compile_program(&name, &input, &program, optimize)?
+    };
+
+    print!("{}", disassemble(&bytecode));
+    Ok(())
+}
+
+// `-` reads from stdin
+fn read_input(path: &str) -> Result<(String, Vec<u8>), String> {
+    if path == "-" {
+        let mut bytes = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut bytes)
+            .map_err(|error| format!("<stdin>: {}", error))?;
+        return Ok(("<stdin>".to_string(), bytes));
+    }
+
+    let bytes = std::fs::read(path).map_err(|error| format!("{}: {}", path, error))?;
+    Ok((path.to_string(), bytes))
+}
+
+// every parse error is reported, one per line, as name:line:column: error[code]: message
+fn parse(name: &str, input: &str) -> Result<Program, String> {
+    Parser::new(Lexer::new(input)).parse_program().map_err(|errors| {
+        let errors: Vec<String> = errors
+            .iter()
+            .map(|error| {
+                let (line, column) = error.range.line_col(input);
+                format!("{}:{}:{}: error[{}]: {}", name, line, column, error.kind.code(), error)
+            })
+            .collect();
+        errors.join("\n")
+    })
+}
+
+// a compile error is reported like a parse error, at the node that couldn't be compiled
+fn compile_program(name: &str, input: &str, program: &Program, optimize: bool) -> Result<Bytecode, String> {
+    let mut compiler = Compiler::new();
+    compiler.set_optimize(optimize);
+    compiler.compile(program).map_err(|error| match compiler.error_range() {
+        Some(range) => {
+            let (line, column) = range.line_col(input);
+            format!("{}:{}:{}: compile error: {}", name, line, column, error)
+        },
+        None => format!("{}: compile error: {}", name, error),
+    })?;
+    Ok(compiler.bytecode())
 }